@@ -0,0 +1,102 @@
+//! 徽章计数热路径的并发吞吐对比：原子计数器 vs 全局 `RwLock<HashMap>`。
+//!
+//! 运行：`cargo bench --bench badge_throughput`
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDateTime;
+use naive::{counter::Counters, now_utc};
+use tokio::sync::RwLock;
+
+const MEMBERS: i64 = 500;
+const TASKS: i64 = 64;
+const HITS_PER_TASK: i64 = 20_000;
+
+type LockedMap = RwLock<HashMap<i64, (i64, NaiveDateTime)>>;
+
+// 旧实现：每次计数都要拿全局写锁，再读一次
+async fn locked_hit(map: &LockedMap, id: i64) -> i64 {
+    let mut write = map.write().await;
+    let mut dist = write
+        .get(&id)
+        .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)))
+        .to_owned();
+    dist.0 += 1;
+    dist.1 = now_utc();
+    write.insert(id, dist);
+    drop(write);
+    map.read().await.get(&id).map(|v| v.0).unwrap_or(0)
+}
+
+fn atomic_hit(counters: &Counters, id: i64) -> i64 {
+    counters.incr_unique_visitor(id, now_utc());
+    counters.get(id).unique_visitor
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let total = (TASKS * HITS_PER_TASK) as f64;
+    println!(
+        "{:<8} {:>10.0} hits/s ({:?} for {} hits, {} members, {} tasks)",
+        name,
+        total / elapsed.as_secs_f64(),
+        elapsed,
+        total,
+        MEMBERS,
+        TASKS
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let ids = (1..=MEMBERS).collect::<Vec<_>>();
+
+    let map: Arc<LockedMap> = Arc::new(RwLock::new(HashMap::new()));
+    let elapsed = runtime.block_on(async {
+        let start = Instant::now();
+        let handles = (0..TASKS)
+            .map(|t| {
+                let map = map.clone();
+                tokio::spawn(async move {
+                    for i in 0..HITS_PER_TASK {
+                        locked_hit(&map, (t * HITS_PER_TASK + i) % MEMBERS + 1).await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.await.unwrap();
+        }
+        start.elapsed()
+    });
+    report("rwlock", elapsed);
+
+    let counters = Arc::new(Counters::new(ids.iter()));
+    let elapsed = runtime.block_on(async {
+        let start = Instant::now();
+        let handles = (0..TASKS)
+            .map(|t| {
+                let counters = counters.clone();
+                tokio::spawn(async move {
+                    for i in 0..HITS_PER_TASK {
+                        atomic_hit(&counters, (t * HITS_PER_TASK + i) % MEMBERS + 1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.await.unwrap();
+        }
+        start.elapsed()
+    });
+    report("atomic", elapsed);
+
+    let total: i64 = counters.snapshot().values().map(|c| c.unique_visitor).sum();
+    assert_eq!(total, TASKS * HITS_PER_TASK);
+}
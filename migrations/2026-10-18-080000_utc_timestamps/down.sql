@@ -0,0 +1,13 @@
+-- 恢复为上海时间（UTC+8）存储，与 up.sql 一样假定时区为 Asia/Shanghai
+UPDATE
+    `statistics`
+SET
+    created_at = datetime(created_at, '+8 hours'),
+    updated_at = CASE
+        WHEN updated_at > '1970-01-02' THEN datetime(updated_at, '+8 hours')
+        ELSE updated_at
+    END,
+    latest_referrer_at = CASE
+        WHEN latest_referrer_at > '1970-01-02' THEN datetime(latest_referrer_at, '+8 hours')
+        ELSE latest_referrer_at
+    END;
@@ -0,0 +1,15 @@
+-- 历史数据均以上海时间（UTC+8）写入，统一转换为 UTC 存储。
+-- 固定减 8 小时的前提是此前的服务一直运行在 Asia/Shanghai（无夏令时）；
+-- 若曾以其他时区运行，需先按当时的偏移改写本文件再迁移
+UPDATE
+    `statistics`
+SET
+    created_at = datetime(created_at, '-8 hours'),
+    updated_at = CASE
+        WHEN updated_at > '1970-01-02' THEN datetime(updated_at, '-8 hours')
+        ELSE updated_at
+    END,
+    latest_referrer_at = CASE
+        WHEN latest_referrer_at > '1970-01-02' THEN datetime(latest_referrer_at, '-8 hours')
+        ELSE latest_referrer_at
+    END;
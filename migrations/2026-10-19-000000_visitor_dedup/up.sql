@@ -0,0 +1,6 @@
+-- 访客去重记录，重启后恢复，避免同一访客在 TTL 内被重复计数
+CREATE TABLE visitor_dedup (
+    visitor_key TEXT PRIMARY KEY NOT NULL,
+    expired_at TIMESTAMP NOT NULL
+);
+CREATE INDEX visitor_dedup_expired_at ON visitor_dedup (expired_at);
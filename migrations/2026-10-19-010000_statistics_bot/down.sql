@@ -0,0 +1,2 @@
+ALTER TABLE
+    `statistics` DROP COLUMN bot;
@@ -0,0 +1,5 @@
+-- 被识别为机器人的访问单独计数，不计入 UV 与 RV
+ALTER TABLE
+    `statistics`
+ADD
+    COLUMN bot BIGINT NOT NULL DEFAULT 0;
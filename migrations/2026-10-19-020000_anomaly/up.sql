@@ -0,0 +1,10 @@
+-- 流量异常标记，每个站点每天一条，excluded 为真时该日数据不计入排行
+CREATE TABLE anomaly (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    membership_id BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    reasons TEXT NOT NULL,
+    excluded BOOLEAN NOT NULL DEFAULT 0,
+    detected_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX idx_anomaly_membership_id ON anomaly (membership_id, created_at);
@@ -0,0 +1,5 @@
+-- 设置了嵌入密钥的站点，未携带有效令牌的徽章访问单独计数，不计入 UV
+ALTER TABLE
+    `statistics`
+ADD
+    COLUMN unverified BIGINT NOT NULL DEFAULT 0;
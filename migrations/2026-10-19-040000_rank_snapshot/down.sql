@@ -0,0 +1 @@
+DROP TABLE rank_snapshot;
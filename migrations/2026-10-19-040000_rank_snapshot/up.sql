@@ -0,0 +1,9 @@
+-- 每日收盘时各站点在总排行与月榜中的名次，未上榜时为空
+CREATE TABLE rank_snapshot (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    membership_id BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    position BIGINT,
+    monthly_position BIGINT
+);
+CREATE UNIQUE INDEX idx_rank_snapshot_membership_id ON rank_snapshot (membership_id, created_at);
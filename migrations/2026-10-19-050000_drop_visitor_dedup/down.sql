@@ -0,0 +1,5 @@
+CREATE TABLE visitor_dedup (
+    visitor_key TEXT PRIMARY KEY NOT NULL,
+    expired_at TIMESTAMP NOT NULL
+);
+CREATE INDEX visitor_dedup_expired_at ON visitor_dedup (expired_at);
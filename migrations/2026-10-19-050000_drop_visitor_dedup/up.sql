@@ -0,0 +1,2 @@
+-- 哈希盐改为只保存在内存中，落库的去重记录重启后无法再匹配，且不应保留，整表删除
+DROP TABLE visitor_dedup;
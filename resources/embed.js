@@ -0,0 +1,28 @@
+// 无聊湾来访统计：为本页指向无聊湾的链接加上 from 参数，
+// 站点设置了 no-referrer 等严格的 Referrer-Policy 时也能记录来访。
+// 用法：<script async src="https://boringbay.com/embed.js" data-domain="example.com"></script>
+(function () {
+  var script = document.currentScript;
+  if (!script) {
+    return;
+  }
+  var bay = new URL(script.src).origin;
+  var from = script.getAttribute("data-domain") || location.hostname;
+
+  function decorate() {
+    var links = document.querySelectorAll('a[href^="' + bay + '"]');
+    for (var i = 0; i < links.length; i++) {
+      var url = new URL(links[i].href);
+      if (!url.searchParams.has("from")) {
+        url.searchParams.set("from", from);
+        links[i].href = url.toString();
+      }
+    }
+  }
+
+  if (document.readyState === "loading") {
+    document.addEventListener("DOMContentLoaded", decorate);
+  } else {
+    decorate();
+  }
+})();
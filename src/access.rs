@@ -0,0 +1,227 @@
+use std::{fmt, fs, net::IpAddr, path::Path};
+
+use anyhow::{anyhow, Context as _};
+use ipnet::IpNet;
+use serde::Deserialize;
+
+/// 访问名单文件的原始内容
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct AccessFile {
+    allow_cidrs: Vec<String>,
+    deny_cidrs: Vec<String>,
+    allow_countries: Vec<String>,
+    deny_countries: Vec<String>,
+}
+
+/// 按 IP 段与国家决定是否计数。判定顺序：
+/// 命中 allow_cidrs 放行；命中 deny_cidrs 拒绝；国家在 deny_countries 中拒绝；
+/// allow_countries 非空且国家不在其中拒绝；其余放行
+#[derive(Debug, Default)]
+pub struct AccessList {
+    allow_cidrs: Vec<IpNet>,
+    deny_cidrs: Vec<IpNet>,
+    allow_countries: Vec<String>,
+    deny_countries: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision {
+    Allow(String),
+    Deny(String),
+}
+
+impl Decision {
+    pub fn is_denied(&self) -> bool {
+        matches!(self, Decision::Deny(_))
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Allow(reason) => write!(f, "allow ({})", reason),
+            Decision::Deny(reason) => write!(f, "deny ({})", reason),
+        }
+    }
+}
+
+impl AccessList {
+    /// 读取名单文件，文件不存在时名单为空，全部放行
+    pub fn load(path: &Path) -> Result<AccessList, anyhow::Error> {
+        if !path.exists() {
+            return Ok(AccessList::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read access list {}", path.display()))?;
+        AccessList::parse(&content)
+            .with_context(|| format!("invalid access list {}", path.display()))
+    }
+
+    fn parse(content: &str) -> Result<AccessList, anyhow::Error> {
+        let file: AccessFile = toml::from_str(content)?;
+        Ok(AccessList {
+            allow_cidrs: parse_cidrs(&file.allow_cidrs)?,
+            deny_cidrs: parse_cidrs(&file.deny_cidrs)?,
+            allow_countries: parse_countries(&file.allow_countries),
+            deny_countries: parse_countries(&file.deny_countries),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.allow_cidrs.len()
+            + self.deny_cidrs.len()
+            + self.allow_countries.len()
+            + self.deny_countries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn decide(&self, ip: &str, country: &str) -> Decision {
+        let addr = ip.trim().parse::<IpAddr>().ok();
+        if let Some(addr) = addr {
+            if let Some(net) = self.allow_cidrs.iter().find(|n| n.contains(&addr)) {
+                return Decision::Allow(format!("cidr {} allowed", net));
+            }
+            if let Some(net) = self.deny_cidrs.iter().find(|n| n.contains(&addr)) {
+                return Decision::Deny(format!("cidr {} denied", net));
+            }
+        }
+        let country = country.trim().to_uppercase();
+        if self.deny_countries.contains(&country) {
+            return Decision::Deny(format!("country {} denied", country));
+        }
+        if !self.allow_countries.is_empty() {
+            if self.allow_countries.contains(&country) {
+                return Decision::Allow(format!("country {} allowed", country));
+            }
+            return Decision::Deny(format!("country {} not allowed", country));
+        }
+        Decision::Allow("default".to_string())
+    }
+}
+
+// 支持单个 IP 与 CIDR 两种写法
+fn parse_cidrs(list: &[String]) -> Result<Vec<IpNet>, anyhow::Error> {
+    list.iter()
+        .map(|s| {
+            let s = s.trim();
+            s.parse::<IpNet>()
+                .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| anyhow!("invalid cidr {}", s))
+        })
+        .collect()
+}
+
+fn parse_countries(list: &[String]) -> Vec<String> {
+    list.iter().map(|c| c.trim().to_uppercase()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个测试用各自的文件，避免并行时互相覆盖
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "boringbay-access-{}-{}.toml",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn decide() {
+        let access = AccessList::parse(
+            r#"
+            allow_cidrs = ["203.0.113.7"]
+            deny_cidrs = ["203.0.113.0/24", "2001:db8::/32"]
+            allow_countries = []
+            deny_countries = ["t1"]
+            "#,
+        )
+        .unwrap();
+        let cases = [
+            // 放行优先于拒绝
+            ("203.0.113.7", "CN", false),
+            ("203.0.113.8", "CN", true),
+            ("2001:db8::1", "US", true),
+            ("2001:db9::1", "US", false),
+            // 国家不区分大小写
+            ("198.51.100.1", "T1", true),
+            ("198.51.100.1", "t1", true),
+            ("198.51.100.1", "CN", false),
+            // 解析不出 IP 时只按国家判定
+            ("unknown", "XX", false),
+        ];
+        for (ip, country, denied) in cases {
+            assert_eq!(
+                access.decide(ip, country).is_denied(),
+                denied,
+                "{} {}",
+                ip,
+                country
+            );
+        }
+    }
+
+    #[test]
+    fn allow_countries_deny_the_rest() {
+        let access = AccessList::parse(r#"allow_countries = ["CN", "HK"]"#).unwrap();
+        assert_eq!(
+            access.decide("198.51.100.1", "cn"),
+            Decision::Allow("country CN allowed".to_string())
+        );
+        assert_eq!(
+            access.decide("198.51.100.1", "US"),
+            Decision::Deny("country US not allowed".to_string())
+        );
+        // 未经可信代理时国家未知，同样拒绝
+        assert!(access.decide("198.51.100.1", "XX").is_denied());
+    }
+
+    #[test]
+    fn empty_list_allows_everything() {
+        let access = AccessList::default();
+        assert!(access.is_empty());
+        assert_eq!(
+            access.decide("203.0.113.8", "T1"),
+            Decision::Allow("default".to_string())
+        );
+    }
+
+    #[test]
+    fn reload_picks_up_changes() {
+        let path = temp_file("reload");
+        // 文件不存在时全部放行
+        let _ = fs::remove_file(&path);
+        assert!(AccessList::load(&path).unwrap().is_empty());
+
+        fs::write(&path, r#"deny_cidrs = ["203.0.113.0/24"]"#).unwrap();
+        let access = AccessList::load(&path).unwrap();
+        assert_eq!(access.len(), 1);
+        assert!(access.decide("203.0.113.8", "CN").is_denied());
+
+        fs::write(&path, r#"deny_countries = ["US"]"#).unwrap();
+        let access = AccessList::load(&path).unwrap();
+        assert!(!access.decide("203.0.113.8", "CN").is_denied());
+        assert!(access.decide("203.0.113.8", "US").is_denied());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reload_rejects_invalid_lists() {
+        let path = temp_file("invalid");
+        for content in [
+            r#"deny_cidrs = ["203.0.113.0/33"]"#,
+            r#"deny_cidrs = ["not an ip"]"#,
+            r#"deny_ips = ["203.0.113.8"]"#,
+        ] {
+            fs::write(&path, content).unwrap();
+            assert!(AccessList::load(&path).is_err(), "{}", content);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use chrono::NaiveDateTime;
+
+use crate::anomaly_model::NewAnomaly;
+use crate::config::AnomalyConfig;
+use crate::now_utc;
+use crate::statistics_model::Statistics;
+
+/// 单个站点当日访客的来源分布，只记录网段哈希与国家，不含 IP
+#[derive(Default)]
+struct MemberSignals {
+    networks: HashSet<String>,
+    countries: HashMap<String, i64>,
+}
+
+/// 某站点一天的来源分布汇总
+#[derive(Debug, Clone, Default)]
+pub struct DaySignals {
+    pub networks: usize,
+    pub countries: HashMap<String, i64>,
+}
+
+/// 全部站点当日的来源分布，只在新访客计数时记录，跨天时取走交给检测
+pub struct TrafficSignals {
+    members: HashMap<i64, Mutex<MemberSignals>>,
+}
+
+impl TrafficSignals {
+    pub fn new<'a>(ids: impl Iterator<Item = &'a i64>) -> Self {
+        Self {
+            members: ids
+                .map(|id| (*id, Mutex::new(MemberSignals::default())))
+                .collect(),
+        }
+    }
+
+    pub fn record(&self, id: i64, network: String, country: &str) {
+        if let Some(m) = self.members.get(&id) {
+            let mut m = m.lock().unwrap();
+            m.networks.insert(network);
+            *m.countries.entry(country.to_string()).or_default() += 1;
+        }
+    }
+
+    /// 取走全部分布并清空，用于跨天
+    pub fn reset(&self) -> HashMap<i64, DaySignals> {
+        self.members
+            .iter()
+            .map(|(id, m)| {
+                let m = std::mem::take(&mut *m.lock().unwrap());
+                (
+                    *id,
+                    DaySignals {
+                        networks: m.networks.len(),
+                        countries: m.countries,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// IP 所在网段，IPv4 取 /24，IPv6 取 /48
+pub fn network_of(ip: &str) -> String {
+    match ip.trim().parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.0/24", o[0], o[1], o[2])
+        }
+        Ok(IpAddr::V6(v6)) => {
+            let s = v6.segments();
+            format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2])
+        }
+        Err(_) => ip.to_string(),
+    }
+}
+
+/// 检测某天的异常站点。`stats` 需包含当天及此前 `history_days` 天的每日统计，
+/// `signals` 为当天的来源分布，服务重启后可能缺失，此时跳过相关检查
+pub fn detect(
+    config: &AnomalyConfig,
+    day: NaiveDateTime,
+    stats: &[Statistics],
+    signals: &HashMap<i64, DaySignals>,
+) -> Vec<NewAnomaly> {
+    let views = |s: &Statistics| s.unique_visitor + s.referrer;
+
+    let today = stats
+        .iter()
+        .filter(|s| s.created_at == day)
+        .collect::<Vec<_>>();
+
+    // 全湾当日访问量的中位数与绝对中位差
+    let mut bay = today
+        .iter()
+        .map(|s| views(s) as f64)
+        .filter(|v| *v > 0.0)
+        .collect::<Vec<_>>();
+    let bay_median = median(&mut bay);
+    let mut deviations = bay
+        .iter()
+        .map(|v| (v - bay_median).abs())
+        .collect::<Vec<_>>();
+    let bay_mad = median(&mut deviations).max(1.0);
+
+    let mut result = Vec::new();
+    for s in today {
+        let v = views(s);
+        if v < config.min_views {
+            continue;
+        }
+        let mut reasons = Vec::new();
+
+        // 与自身历史比较
+        let history = stats
+            .iter()
+            .filter(|h| h.membership_id == s.membership_id && h.created_at < day)
+            .map(|h| views(h) as f64)
+            .collect::<Vec<_>>();
+        if history.len() >= 3 {
+            let mean = history.iter().sum::<f64>() / history.len() as f64;
+            let std = (history.iter().map(|h| (h - mean).powi(2)).sum::<f64>()
+                / history.len() as f64)
+                .sqrt()
+                .max(1.0);
+            if v as f64 > mean + config.history_sigma * std {
+                reasons.push(format!(
+                    "访问量 {} 远高于近 {} 天均值 {:.0}",
+                    v,
+                    history.len(),
+                    mean
+                ));
+            }
+        }
+
+        // 与全湾分布比较
+        if bay.len() >= 5 {
+            let score = (v as f64 - bay_median) / (1.4826 * bay_mad);
+            if score > config.bay_score {
+                reasons.push(format!("访问量 {} 远高于全湾中位数 {:.0}", v, bay_median));
+            }
+        }
+
+        if let Some(signal) = signals.get(&s.membership_id) {
+            let visitors = signal.countries.values().sum::<i64>();
+            if visitors >= config.min_views {
+                let ratio = signal.networks as f64 / visitors as f64;
+                if ratio < config.min_network_ratio {
+                    reasons.push(format!(
+                        "{} 位访客仅来自 {} 个网段",
+                        visitors, signal.networks
+                    ));
+                }
+                let entropy = entropy(&signal.countries);
+                if entropy > config.max_country_entropy {
+                    reasons.push(format!(
+                        "访客来自 {} 个国家和地区，分布熵 {:.2}",
+                        signal.countries.len(),
+                        entropy
+                    ));
+                }
+            }
+        }
+
+        if !reasons.is_empty() {
+            result.push(NewAnomaly {
+                membership_id: s.membership_id,
+                created_at: day,
+                reasons: reasons.join("；"),
+                detected_at: now_utc(),
+            });
+        }
+    }
+    result
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+// 香农熵（比特）
+fn entropy(counts: &HashMap<String, i64>) -> f64 {
+    let total = counts.values().sum::<i64>() as f64;
+    if total <= 0.0 {
+        return 0.0;
+    }
+    counts
+        .values()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    fn day(offset: i64) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(16, 0, 0)
+            .unwrap()
+            + Duration::days(offset)
+    }
+
+    fn stat(id: i64, offset: i64, views: i64) -> Statistics {
+        Statistics {
+            id: 0,
+            created_at: day(offset),
+            updated_at: day(offset),
+            membership_id: id,
+            unique_visitor: views,
+            referrer: 0,
+            latest_referrer_at: day(offset),
+            bot: 0,
+            unverified: 0,
+        }
+    }
+
+    // 站点 id 当天按自身历史检测，历史为此前逐日的访问量
+    fn history_flagged(history: &[i64], today: i64) -> bool {
+        let mut stats = history
+            .iter()
+            .enumerate()
+            .map(|(i, v)| stat(1, i as i64 - history.len() as i64, *v))
+            .collect::<Vec<_>>();
+        stats.push(stat(1, 0, today));
+        !detect(&AnomalyConfig::default(), day(0), &stats, &HashMap::new()).is_empty()
+    }
+
+    // 站点 1 为 today，其余站点为 others，全部只有当天数据
+    fn bay_flagged(others: &[i64], today: i64) -> bool {
+        let mut stats = others
+            .iter()
+            .enumerate()
+            .map(|(i, v)| stat(i as i64 + 2, 0, *v))
+            .collect::<Vec<_>>();
+        stats.push(stat(1, 0, today));
+        detect(&AnomalyConfig::default(), day(0), &stats, &HashMap::new())
+            .iter()
+            .any(|a| a.membership_id == 1)
+    }
+
+    fn signals_flagged(networks: usize, countries: &[(&str, i64)]) -> Option<String> {
+        let signals = HashMap::from([(
+            1,
+            DaySignals {
+                networks,
+                countries: countries.iter().map(|(c, n)| (c.to_string(), *n)).collect(),
+            },
+        )]);
+        detect(
+            &AnomalyConfig::default(),
+            day(0),
+            &[stat(1, 0, 100)],
+            &signals,
+        )
+        .pop()
+        .map(|a| a.reasons)
+    }
+
+    #[test]
+    fn median_of_values() {
+        let cases: [(&[f64], f64); 5] = [
+            (&[], 0.0),
+            (&[3.0], 3.0),
+            (&[5.0, 1.0, 3.0], 3.0),
+            (&[4.0, 1.0, 3.0, 2.0], 2.5),
+            (&[7.0, 7.0, 7.0, 7.0], 7.0),
+        ];
+        for (values, expected) in cases {
+            assert_eq!(median(&mut values.to_vec()), expected, "{:?}", values);
+        }
+    }
+
+    #[test]
+    fn entropy_of_countries() {
+        let counts = |list: &[(&str, i64)]| {
+            list.iter()
+                .map(|(c, n)| (c.to_string(), *n))
+                .collect::<HashMap<_, _>>()
+        };
+        let cases: [(&[(&str, i64)], f64); 5] = [
+            (&[], 0.0),
+            (&[("CN", 100)], 0.0),
+            (&[("CN", 50), ("US", 50)], 1.0),
+            (&[("CN", 1), ("US", 1), ("JP", 1), ("DE", 1)], 2.0),
+            // 计数为 0 的国家不参与
+            (&[("CN", 50), ("US", 50), ("JP", 0)], 1.0),
+        ];
+        for (list, expected) in cases {
+            assert!(
+                (entropy(&counts(list)) - expected).abs() < 1e-9,
+                "{:?}",
+                list
+            );
+        }
+    }
+
+    #[test]
+    fn history_sigma() {
+        let cases: [(&[i64], i64, bool); 6] = [
+            // 均值 100，标准差约 6.3，阈值约 125
+            (&[100, 110, 90, 100, 100], 200, true),
+            (&[100, 110, 90, 100, 100], 120, false),
+            // 历史恒定时标准差按 1 计，阈值 104
+            (&[100, 100, 100], 105, true),
+            (&[100, 100, 100], 104, false),
+            // 历史不足 3 天不比较
+            (&[60, 60], 5000, false),
+            (&[], 5000, false),
+        ];
+        for (history, today, flagged) in cases {
+            assert_eq!(
+                history_flagged(history, today),
+                flagged,
+                "{:?} -> {}",
+                history,
+                today
+            );
+        }
+    }
+
+    #[test]
+    fn bay_robust_z_score() {
+        let cases: [(&[i64], i64, bool); 6] = [
+            // 中位数 100，绝对中位差约 10
+            (&[80, 90, 100, 110, 120], 1000, true),
+            (&[80, 90, 100, 110, 120], 180, false),
+            // 绝对中位差为 0 时按 1 计，z = (v - 100) / 1.4826
+            (&[100, 100, 100, 100, 100], 109, true),
+            (&[100, 100, 100, 100, 100], 108, false),
+            // 有访问的站点不足 5 个不比较
+            (&[100, 100, 100], 100_000, false),
+            (&[100, 100, 100, 0, 0], 100_000, false),
+        ];
+        for (others, today, flagged) in cases {
+            assert_eq!(
+                bay_flagged(others, today),
+                flagged,
+                "{:?} -> {}",
+                others,
+                today
+            );
+        }
+    }
+
+    #[test]
+    fn below_min_views_is_ignored() {
+        assert!(!history_flagged(&[1, 1, 1, 1], 49));
+        assert!(!bay_flagged(&[1, 1, 1, 1, 1], 49));
+    }
+
+    #[test]
+    fn network_ratio() {
+        // 100 位访客只来自 5 个网段
+        let reasons = signals_flagged(5, &[("CN", 100)]).unwrap();
+        assert!(reasons.contains("5 个网段"), "{}", reasons);
+        assert_eq!(signals_flagged(50, &[("CN", 100)]), None);
+        // 访客数不足 min_views 不比较
+        assert_eq!(signals_flagged(1, &[("CN", 49)]), None);
+    }
+
+    #[test]
+    fn country_entropy() {
+        // 32 个国家均匀分布，熵为 5 比特
+        let many = (0..32).map(|i| (format!("C{}", i), 4)).collect::<Vec<_>>();
+        let many = many
+            .iter()
+            .map(|(c, n)| (c.as_str(), *n))
+            .collect::<Vec<_>>();
+        let reasons = signals_flagged(128, &many).unwrap();
+        assert!(reasons.contains("32 个国家和地区"), "{}", reasons);
+        // 4 个国家，熵为 2 比特
+        assert_eq!(
+            signals_flagged(100, &[("CN", 25), ("US", 25), ("JP", 25), ("DE", 25)]),
+            None
+        );
+    }
+
+    #[test]
+    fn missing_signals_skip_source_checks() {
+        assert!(detect(
+            &AnomalyConfig::default(),
+            day(0),
+            &[stat(1, 0, 100)],
+            &HashMap::new()
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn network_of_ip() {
+        assert_eq!(network_of("203.0.113.77"), "203.0.113.0/24");
+        assert_eq!(network_of(" 2001:db8:1:2::1 "), "2001:db8:1::/48");
+        assert_eq!(network_of("unknown"), "unknown");
+    }
+}
@@ -0,0 +1,58 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{Queryable, SqliteConnection};
+
+use crate::error::AppError;
+use crate::schema::anomaly::{self, dsl::*};
+
+/// 某站点某天的流量异常标记，`created_at` 为当日零点（UTC）
+#[derive(Queryable, Debug, Clone, serde::Serialize)]
+pub struct Anomaly {
+    pub id: i32,
+    pub membership_id: i64,
+    pub created_at: NaiveDateTime,
+    pub reasons: String,
+    pub excluded: bool,
+    pub detected_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = anomaly)]
+pub struct NewAnomaly {
+    pub membership_id: i64,
+    pub created_at: NaiveDateTime,
+    pub reasons: String,
+    pub detected_at: NaiveDateTime,
+}
+
+impl Anomaly {
+    /// 同一天重复检测时只更新原因，保留管理员的排除设置
+    pub fn insert_or_update(
+        conn: &mut SqliteConnection,
+        item: &NewAnomaly,
+    ) -> Result<usize, AppError> {
+        Ok(diesel::insert_into(anomaly)
+            .values(item)
+            .on_conflict((membership_id, created_at))
+            .do_update()
+            .set((reasons.eq(&item.reasons), detected_at.eq(item.detected_at)))
+            .execute(conn)?)
+    }
+
+    pub fn all(conn: &mut SqliteConnection) -> Result<Vec<Anomaly>, AppError> {
+        Ok(anomaly
+            .order_by(created_at.desc())
+            .then_order_by(membership_id)
+            .load::<Anomaly>(conn)?)
+    }
+
+    pub fn set_excluded(
+        conn: &mut SqliteConnection,
+        _id: i32,
+        value: bool,
+    ) -> Result<usize, AppError> {
+        Ok(diesel::update(anomaly.find(_id))
+            .set(excluded.eq(value))
+            .execute(conn)?)
+    }
+}
@@ -1,14 +1,30 @@
 use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
 
+use crate::access::AccessList;
+use crate::anomaly::{self, DaySignals, TrafficSignals};
+use crate::anomaly_model::Anomaly;
+use crate::bot;
+use crate::counter::{Counters, MemberCount};
+use crate::dedup::DedupCache;
+use crate::embed_token;
+use crate::rank_snapshot_model::{NewRankSnapshot, RankSnapshot};
 use crate::statistics_model::Statistics;
-use crate::{boring_face::BoringFace, DbPool};
-use crate::{now_shanghai, SYSTEM_DOMAIN};
+use crate::{
+    boring_face::BoringFace, boring_face::Palette, boring_face::Standing, config::Config,
+    error::AppError, DbPool,
+};
+use crate::{day_start_of, days_between, now_utc, today_start, with_conn};
 
 use crate::membership_model::Membership;
-use anyhow::anyhow;
-use chrono::{NaiveDateTime, NaiveTime};
+use crate::raster::Rasterizer;
+use crate::rate_limit::{Client, RateLimiter};
+use anyhow::{bail, Context as _};
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
 use headers::HeaderMap;
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -16,10 +32,13 @@ use serde::Serialize;
 use serde_repr::*;
 use tokio::sync::watch::{self, Receiver, Sender};
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{error, info};
 
 pub type DynContext = Arc<Context>;
 
+// spark 徽章可展示的最长天数
+pub const HISTORY_DAYS: usize = 30;
+
 lazy_static! {
     static ref IPV4_MASK: Regex = Regex::new("(\\d*\\.).*(\\.\\d*)").unwrap();
     static ref IPV6_MASK: Regex = Regex::new("(\\w*:\\w*:).*(:\\w*:\\w*)").unwrap();
@@ -33,6 +52,37 @@ struct VistEvent {
     vt: Option<VisitorType>,
 }
 
+/// 一次访问的计数判定过程，explain 模式下通过 X-Boringbay-Decision 响应头返回
+#[derive(Debug, Default)]
+pub struct Decision {
+    /// 返回判定过程
+    pub explain: bool,
+    /// 只判定不计数
+    pub preview: bool,
+    steps: Vec<String>,
+}
+
+impl Decision {
+    pub fn new(explain: bool, preview: bool) -> Self {
+        Self {
+            explain: explain || preview,
+            preview,
+            steps: Vec::new(),
+        }
+    }
+
+    /// 只在 explain 模式下生成并记录，计数的热路径上不产生额外分配
+    pub fn step<S: Into<String>>(&mut self, step: impl FnOnce() -> S) {
+        if self.explain {
+            self.steps.push(step().into());
+        }
+    }
+
+    pub fn to_header(&self) -> String {
+        self.steps.join("; ")
+    }
+}
+
 #[derive(Serialize_repr, Debug, PartialEq, Clone, Copy)]
 #[repr(u8)]
 pub enum VisitorType {
@@ -42,14 +92,15 @@ pub enum VisitorType {
 }
 
 pub struct Context {
+    pub config: Config,
+
     pub badge: BoringFace,
     pub favicon: BoringFace,
     pub icon: BoringFace,
 
     pub db_pool: DbPool,
-    pub unique_visitor: RwLock<HashMap<i64, (i64, NaiveDateTime)>>,
-    pub referrer: RwLock<HashMap<i64, (i64, NaiveDateTime)>>,
-    pub rank_svg: RwLock<i64>,
+    pub counter: Counters,
+    pub rank_svg: AtomicI64,
 
     pub domain2id: HashMap<String, i64>,
     pub id2member: HashMap<i64, Membership>,
@@ -59,13 +110,23 @@ pub struct Context {
 
     pub rank: RwLock<Vec<Statistics>>,
     pub monthly_rank: RwLock<Vec<Statistics>>,
-
-    pub cache: r_cache::cache::Cache<String, ()>,
+    pub anomalies: RwLock<Vec<Anomaly>>,
+    /// 各站点此前 HISTORY_DAYS 天逐日的 UV+RV，由旧到新，日切时刷新
+    pub history: std::sync::RwLock<HashMap<i64, Vec<i64>>>,
+    /// 昨日收盘时的名次，日切时刷新
+    pub rank_snapshot: std::sync::RwLock<HashMap<i64, RankSnapshot>>,
+
+    pub cache: DedupCache,
+    pub signals: TrafficSignals,
+
+    pub access: std::sync::RwLock<AccessList>,
+    pub limiter: RateLimiter,
+    pub raster: Rasterizer,
 }
 
 impl Context {
-    pub async fn get_tend_from_uv_and_rv(&self, uv: i64, rv: i64) -> i64 {
-        let tend = (uv + rv) / self.rank_svg.read().await.to_owned();
+    pub fn get_tend_from_uv_and_rv(&self, uv: i64, rv: i64) -> i64 {
+        let tend = (uv + rv) / self.rank_svg.load(Ordering::Relaxed);
         if tend > 10 {
             return 10;
         } else if tend < 1 {
@@ -79,110 +140,199 @@ impl Context {
         v_type: Option<VisitorType>,
         domain: &str,
         headers: &HeaderMap,
-    ) -> Result<(&str, i64, i64, i64), anyhow::Error> {
-        if v_type.is_some_and(|v| v == VisitorType::Referer) && domain.eq(&*SYSTEM_DOMAIN) {
-            return Err(anyhow!("system domain"));
+        client: &Client,
+        token: Option<&str>,
+        decision: &mut Decision,
+    ) -> Result<(&str, i64, i64, i64), AppError> {
+        if v_type.is_some_and(|v| v == VisitorType::Referer)
+            && domain.eq(&self.config.server.system_domain)
+        {
+            decision.step(|| "system domain, not counted");
+            return Err(AppError::SystemDomain);
+        }
+        let Some(id) = self.domain2id.get(domain) else {
+            decision.step(|| format!("{} is not a member", domain));
+            return Err(AppError::NotMember);
+        };
+        let member = self.id2member.get(id).ok_or(AppError::NotMember)?;
+        decision.step(|| format!("member {}", id));
+
+        // 已按可信代理解析，直连的客户端无法伪造
+        let Client { ip, country } = client;
+        info!("ip {}", mask_ip(ip));
+        info!("country {}", country);
+
+        let Some(v_type) = v_type else {
+            decision.step(|| "display only, not counted");
+            return Ok(self.current(&member.name, *id));
+        };
+        decision.step(|| format!("type {:?}", v_type));
+
+        // 名单拒绝的访问不计数也不广播，徽章照常展示
+        let access = self.access.read().unwrap().decide(ip, country);
+        info!("access {} {} {}", mask_ip(ip), country, access);
+        decision.step(|| format!("access {}", access));
+        if access.is_denied() {
+            return Ok(self.current(&member.name, *id));
         }
-        if let Some(id) = self.domain2id.get(domain) {
-            let ip =
-                String::from_utf8(headers.get("CF-Connecting-IP").unwrap().as_bytes().to_vec())
-                    .unwrap();
-            info!("ip {}", ip);
-
-            let country =
-                String::from_utf8(headers.get("CF-IPCountry").unwrap().as_bytes().to_vec())
-                    .unwrap();
-            info!("country {}", country);
-
-            let visitor_key = format!("{}_{}_{:?}", ip, id, v_type);
-            let visitor_cache = self.cache.get(&visitor_key).await;
-
-            if v_type.is_some_and(|v| [VisitorType::Referer, VisitorType::Badge].contains(&v))
-                && visitor_cache.is_none()
-            {
-                self.cache
-                    .set(visitor_key, (), Some(Duration::from_secs(60 * 60 * 4)))
-                    .await;
-            }
 
-            let mut notification = false;
-
-            let mut referrer = self.referrer.write().await;
-            let mut dist_r = referrer
-                .get(id)
-                .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)))
-                .to_owned();
-            if v_type.is_some_and(|v| v == VisitorType::Referer) {
-                if visitor_cache.is_none() {
-                    dist_r.0 += 1;
-                    dist_r.1 = now_shanghai();
-                    referrer.insert(*id, dist_r);
-                }
-                notification = true;
+        if v_type == VisitorType::ICON {
+            decision.step(|| "icon, not counted");
+            return Ok(self.current(&member.name, *id));
+        }
+
+        // 机器人只单独计数，不计入 UV 与 RV，也不广播
+        if let Some(reason) = bot::classify(headers) {
+            info!("bot {:?}", reason);
+            decision.step(|| format!("bot {:?}, counted as bot", reason));
+            if !decision.preview {
+                self.counter.incr_bot(*id);
             }
-            drop(referrer);
-
-            let mut uv = self.unique_visitor.write().await;
-            let mut dist_uv = uv
-                .get(id)
-                .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)))
-                .to_owned();
-            if v_type.is_some_and(|v| v == VisitorType::Badge) {
-                if visitor_cache.is_none() {
-                    dist_uv.0 += 1;
-                    dist_uv.1 = now_shanghai();
-                    uv.insert(*id, dist_uv);
+            return Ok(self.current(&member.name, *id));
+        }
+
+        // 设置了嵌入密钥的站点，徽章访问需携带有效令牌才计入 UV
+        if v_type == VisitorType::Badge {
+            if let Some(secret) = self.config.embed.secrets.get(domain) {
+                if let Err(reason) =
+                    embed_token::verify(secret, domain, token, now_utc().timestamp())
+                {
+                    info!("unverified badge {}: {}", domain, reason);
+                    decision.step(|| format!("{}, counted as unverified", reason));
+                    if !decision.preview {
+                        self.counter.incr_unverified(*id);
+                    }
+                    return Ok(self.current(&member.name, *id));
                 }
-                notification = true;
+                decision.step(|| "token ok");
             }
-            drop(uv);
-
-            let tend = self.get_tend_from_uv_and_rv(dist_uv.0, dist_r.0).await;
-
-            if notification {
-                let mut member = self.id2member.get(id).unwrap().to_owned();
-                member.description = "".to_string();
-                member.icon = "".to_string();
-                member.github_username = "".to_string();
-
-                let _ = self.visitor_tx.send(
-                    serde_json::json!(VistEvent {
-                        ip: IPV6_MASK
-                            .replace_all(&IPV4_MASK.replace_all(&ip, "$1****$2"), "$1****$2")
-                            .to_string(),
-                        country,
-                        member,
-                        vt: v_type,
-                    })
-                    .to_string(),
-                );
+        }
+
+        let user_agent = header_value(headers, "User-Agent").unwrap_or_default();
+        let visitor_key = format!(
+            "{}_{}_{:?}",
+            self.cache.visitor_hash(ip, &user_agent),
+            id,
+            Some(v_type)
+        );
+        let visited = self.cache.contains(&visitor_key).await;
+        let opted_out = privacy_opted_out(headers);
+
+        if visited {
+            decision.step(|| "already counted within dedup ttl");
+        } else if decision.preview {
+            decision.step(|| "new visitor, preview only, not counted");
+        } else {
+            self.cache.insert(visitor_key).await;
+            // 网段同样只记哈希
+            self.signals.record(
+                *id,
+                self.cache.visitor_hash(&anomaly::network_of(ip), ""),
+                country,
+            );
+            match v_type {
+                VisitorType::Referer => self.counter.incr_referrer(*id, now_utc()),
+                _ => self.counter.incr_unique_visitor(*id, now_utc()),
             }
+            decision.step(|| match v_type {
+                VisitorType::Referer => "counted as rv",
+                _ => "counted as uv",
+            });
+        }
 
-            return Ok((
-                &self.id2member.get(id).unwrap().name,
-                dist_uv.0,
-                dist_r.0,
-                tend,
-            ));
+        if opted_out && self.config.privacy.skip_broadcast {
+            decision.step(|| "do not track, not broadcast");
+        } else if !decision.preview {
+            let mut member = member.to_owned();
+            member.description = "".to_string();
+            member.icon = "".to_string();
+            member.github_username = "".to_string();
+
+            let _ = self.visitor_tx.send(
+                serde_json::json!(VistEvent {
+                    ip: mask_ip(ip),
+                    country: country.to_string(),
+                    member,
+                    vt: Some(v_type),
+                })
+                .to_string(),
+            );
         }
-        Err(anyhow!("not a member"))
+
+        Ok(self.current(&member.name, *id))
     }
 
-    pub async fn default(db_pool: DbPool) -> Context {
-        let statistics = Statistics::today(db_pool.get().unwrap()).unwrap_or_default();
+    /// 渲染配色：`?theme=` 指定的内置配色优先，其次是站点的品牌色，都没有时使用配置中的配色
+    pub fn palette(&self, domain: &str, theme: Option<&str>) -> Option<Palette> {
+        theme.and_then(Palette::named).or_else(|| {
+            let member = self.id2member.get(self.domain2id.get(domain)?)?;
+            Palette::from_brand(member.brand_color.as_deref()?)
+        })
+    }
 
-        let mut page_view: HashMap<i64, (i64, NaiveDateTime)> = HashMap::new();
-        let mut referrer: HashMap<i64, (i64, NaiveDateTime)> = HashMap::new();
+    /// 最近 days 天逐日的 UV+RV，由旧到新，最后一天为今天的实时计数
+    pub fn series(&self, domain: &str, days: usize) -> Vec<i64> {
+        let days = days.clamp(1, HISTORY_DAYS + 1);
+        let Some(id) = self.domain2id.get(domain) else {
+            return vec![0; days];
+        };
+        let mut series = match self.history.read().unwrap().get(id) {
+            Some(history) => history[HISTORY_DAYS + 1 - days..].to_vec(),
+            None => vec![0; days - 1],
+        };
+        let count = self.counter.get(*id);
+        series.push(count.unique_visitor + count.referrer);
+        series
+    }
 
-        statistics.iter().for_each(|s| {
-            page_view.insert(s.membership_id, (s.unique_visitor, s.updated_at));
-            referrer.insert(s.membership_id, (s.referrer, s.latest_referrer_at));
-        });
+    /// 只读取站点当前的计数与等级，不计数
+    pub fn peek(&self, domain: &str) -> Result<(&str, i64, i64, i64), AppError> {
+        let id = self.domain2id.get(domain).ok_or(AppError::NotMember)?;
+        let member = self.id2member.get(id).ok_or(AppError::NotMember)?;
+        Ok(self.current(&member.name, *id))
+    }
+
+    // 站点当前的计数与等级
+    fn current<'a>(&self, name: &'a str, id: i64) -> (&'a str, i64, i64, i64) {
+        let count = self.counter.get(id);
+        let tend = self.get_tend_from_uv_and_rv(count.unique_visitor, count.referrer);
+        (name, count.unique_visitor, count.referrer, tend)
+    }
 
-        let mut membership: HashMap<i64, Membership> =
-            serde_json::from_str(&fs::read_to_string("./resources/membership.json").unwrap())
-                .unwrap();
+    /// 重新加载访问名单，失败时保留原名单
+    pub fn reload_access(&self) -> Result<usize, anyhow::Error> {
+        let access = AccessList::load(Path::new(&self.config.server.access_path))?;
+        let rules = access.len();
+        *self.access.write().unwrap() = access;
+        info!("access list reloaded, {} rules", rules);
+        Ok(rules)
+    }
+
+    pub async fn default(db_pool: DbPool, config: Config) -> Result<Context, anyhow::Error> {
+        let tz = config.server.timezone;
+        let statistics = with_conn(&db_pool, move |conn| Statistics::today(conn, tz))
+            .await
+            .unwrap_or_default();
+
+        let membership_path = &config.server.membership_path;
+        let mut membership: HashMap<i64, Membership> = serde_json::from_str(
+            &fs::read_to_string(membership_path)
+                .with_context(|| format!("failed to read {}", membership_path))?,
+        )
+        .with_context(|| format!("invalid membership file {}", membership_path))?;
         membership.retain(|_, v| v.hidden.is_none() || !v.hidden.unwrap());
+        if let Some(m) = membership.values().find(|m| {
+            m.brand_color
+                .as_deref()
+                .is_some_and(|c| Palette::from_brand(c).is_none())
+        }) {
+            bail!(
+                "invalid brand_color {:?} of {} in {}",
+                m.brand_color.as_deref().unwrap_or_default(),
+                m.domain,
+                membership_path
+            );
+        }
 
         let mut domain2id: HashMap<String, i64> = HashMap::new();
         membership.iter_mut().for_each(|(k, v)| {
@@ -190,35 +340,92 @@ impl Context {
             domain2id.insert(v.domain.clone(), *k);
         });
 
-        let rank = Statistics::rank_between(
-            db_pool.get().unwrap(),
-            NaiveDateTime::from_timestamp(0, 0),
-            now_shanghai(),
-        )
-        .unwrap();
+        let access = AccessList::load(Path::new(&config.server.access_path))?;
+        info!("access list loaded, {} rules", access.len());
 
-        let monthly_rank = Statistics::rank_between(
-            db_pool.get().unwrap(),
-            now_shanghai() - chrono::Duration::days(30),
-            now_shanghai(),
-        )
-        .unwrap();
+        let counter = Counters::new(membership.keys());
+        let signals = TrafficSignals::new(membership.keys());
+        statistics.iter().for_each(|s| {
+            counter.restore(
+                s.membership_id,
+                MemberCount {
+                    unique_visitor: s.unique_visitor,
+                    updated_at: s.updated_at,
+                    referrer: s.referrer,
+                    latest_referrer_at: s.latest_referrer_at,
+                    bot: s.bot,
+                    unverified: s.unverified,
+                },
+            );
+        });
 
-        let (visitor_tx, visitor_rx) = watch::channel::<String>("".to_string());
+        let (rank, monthly_rank) = load_rank(&db_pool, config.rank.stale_days)
+            .await
+            .context("failed to load rank")?;
+        let anomalies = with_conn(&db_pool, |conn| Anomaly::all(conn))
+            .await
+            .context("failed to load anomalies")?;
+        let history = load_history(&db_pool, tz)
+            .await
+            .context("failed to load history")?;
+        let yesterday = day_start_of(tz, today_start(tz) - chrono::Duration::hours(1));
+        let rank_snapshot = with_conn(&db_pool, move |conn| RankSnapshot::of_day(conn, yesterday))
+            .await
+            .context("failed to load rank snapshot")?
+            .into_iter()
+            .map(|s| (s.membership_id, s))
+            .collect();
 
-        let rank_svg = Statistics::prev_day_rank_avg(db_pool.get().unwrap());
+        let (visitor_tx, visitor_rx) = watch::channel::<String>("".to_string());
 
-        Context {
-            badge: BoringFace::new("#d0273e".to_string(), "#f5acb9".to_string(), true),
-            favicon: BoringFace::new("#f5acb9".to_string(), "#d0273e".to_string(), false),
-            icon: BoringFace::new("#d0273e".to_string(), "#f5acb9".to_string(), false),
+        let level_divisor = config.counter.level_divisor;
+        let rank_svg = with_conn(&db_pool, move |conn| {
+            Ok(Statistics::prev_day_rank_avg(conn, tz, level_divisor))
+        })
+        .await?;
+
+        let colors = &config.badge;
+        let badge = BoringFace::new(
+            colors.badge.fill_white.clone(),
+            colors.badge.fill_black.clone(),
+            true,
+        );
+        let favicon = BoringFace::new(
+            colors.favicon.fill_white.clone(),
+            colors.favicon.fill_black.clone(),
+            false,
+        );
+        let icon = BoringFace::new(
+            colors.icon.fill_white.clone(),
+            colors.icon.fill_black.clone(),
+            false,
+        );
+        let cache = DedupCache::new(
+            Duration::from_secs(config.counter.dedup_ttl_secs),
+            Duration::from_secs(config.counter.cache_sweep_secs),
+        );
+
+        let limiter = RateLimiter::new(&config.rate_limit);
+        let raster = Rasterizer::new(
+            &config.badge.font_dir,
+            Duration::from_secs(config.badge.png_cache_secs),
+        );
+
+        Ok(Context {
+            config,
+
+            badge,
+            favicon,
+            icon,
             db_pool,
 
-            unique_visitor: RwLock::new(page_view),
-            referrer: RwLock::new(referrer),
-            rank_svg: RwLock::new(rank_svg),
+            counter,
+            rank_svg: AtomicI64::new(rank_svg),
             rank: RwLock::new(rank),
             monthly_rank: RwLock::new(monthly_rank),
+            anomalies: RwLock::new(anomalies),
+            history: std::sync::RwLock::new(history),
+            rank_snapshot: std::sync::RwLock::new(rank_snapshot),
 
             domain2id,
             id2member: membership,
@@ -226,101 +433,288 @@ impl Context {
             visitor_rx,
             visitor_tx,
 
-            cache: r_cache::cache::Cache::new(Some(Duration::from_secs(60 * 10))),
-        }
+            cache,
+            signals,
+
+            access: std::sync::RwLock::new(access),
+            limiter,
+            raster,
+        })
     }
 
-    // 每五分钟存一次，发现隔天刷新
-    pub async fn save_per_5_minutes(&self) {
-        let mut uv_cache: HashMap<i64, (i64, NaiveDateTime)> = HashMap::new();
-        let mut referrer_cache: HashMap<i64, (i64, NaiveDateTime)> = HashMap::new();
-        let mut changed_list: Vec<i64> = Vec::new();
-        let mut _today = NaiveDateTime::new(now_shanghai().date(), NaiveTime::from_hms(0, 0, 0));
-        let id_list = Vec::from_iter(self.id2member.keys());
+    // 定时（默认每五分钟）存一次，发现隔天刷新
+    // 锁内只做快照，数据库读写都在锁外进行，避免阻塞计数
+    pub async fn save_periodically(&self) {
+        let mut saved: HashMap<i64, MemberCount> = HashMap::new();
+        let tz = self.config.server.timezone;
+        let mut _today = today_start(tz);
+        // 已清零但尚未写入数据库的往日数据，写入成功前每轮重试，不会丢失
+        let mut closing: Vec<(NaiveDateTime, Vec<Statistics>, HashMap<i64, DaySignals>)> =
+            Vec::new();
         loop {
-            tokio::time::sleep(Duration::from_secs(60 * 5)).await;
-            changed_list.clear();
-            // 对比是否有数据更新
-            let mut uv_write = self.unique_visitor.write().await;
-            let mut referrer_write = self.referrer.write().await;
-            id_list.iter().for_each(|id| {
-                let uv = *uv_cache
-                    .get(id)
-                    .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-                let new_uv = *uv_write
-                    .get(id)
-                    .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-                if uv.0.ne(&new_uv.0) {
-                    uv_cache.insert(**id, new_uv);
-                    changed_list.push(**id);
-                }
-                let referrer = *referrer_cache
-                    .get(id)
-                    .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-                let new_referrer = *referrer_write
-                    .get(id)
-                    .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-                if referrer.0.ne(&new_referrer.0) {
-                    referrer_cache.insert(**id, new_referrer);
-                    if !changed_list.contains(id) {
-                        changed_list.push(**id);
-                    }
-                }
-            });
-            // 更新到数据库
-            changed_list.iter().for_each(|id| {
-                let id_uv = *uv_cache
-                    .get(id)
-                    .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-                let id_referrer = *referrer_cache
-                    .get(id)
-                    .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-                Statistics::insert_or_update(
-                    self.db_pool.get().unwrap(),
-                    &Statistics {
-                        created_at: _today,
-                        membership_id: *id,
-                        unique_visitor: id_uv.0,
-                        updated_at: id_uv.1,
-                        referrer: id_referrer.0,
-                        latest_referrer_at: id_referrer.1,
-                        id: 0,
-                    },
-                )
-                .unwrap();
-            });
-            let new_day = NaiveDateTime::new(now_shanghai().date(), NaiveTime::from_hms(0, 0, 0));
+            tokio::time::sleep(Duration::from_secs(self.config.counter.flush_interval_secs)).await;
+
+            let new_day = today_start(tz);
             if new_day.ne(&_today) {
+                // 跨天时取走昨日数据并清零，重置访问打点
+                let snapshot = self.counter.reset();
+                closing.push((
+                    _today,
+                    changed_statistics(&saved, &snapshot, _today),
+                    self.signals.reset(),
+                ));
                 _today = new_day;
-                // 如果是跨天重置数据
-                uv_write.clear();
-                referrer_write.clear();
-                uv_cache.clear();
-                referrer_cache.clear();
-                // 重置访问打点
+                saved.clear();
                 self.cache.clear().await;
-                // 更新上日访问量均值
-                let mut rank_svg = self.rank_svg.write().await;
-                *rank_svg = Statistics::prev_day_rank_avg(self.db_pool.get().unwrap());
             }
-            drop(uv_write);
-            drop(referrer_write);
-
-            let mut rank = self.rank.write().await;
-            *rank = Statistics::rank_between(
-                self.db_pool.get().unwrap(),
-                NaiveDateTime::from_timestamp(0, 0),
-                now_shanghai(),
-            )
-            .unwrap();
-
-            let mut monthly_rank = self.monthly_rank.write().await;
-            *monthly_rank = Statistics::rank_between(
-                self.db_pool.get().unwrap(),
-                now_shanghai() - chrono::Duration::days(30),
-                now_shanghai(),
-            )
-            .unwrap();
+
+            // 往日数据按日期先后写入，某天失败时连同之后的留到下一轮
+            let mut closed = Vec::new();
+            while let Some((day, list, _)) = closing.first() {
+                match save_statistics(&self.db_pool, list.clone()).await {
+                    Ok(()) => closed.push(closing.remove(0)),
+                    Err(e) => {
+                        error!("failed to save statistics of {}, will retry: {}", day, e);
+                        break;
+                    }
+                }
+            }
+
+            // 更新到数据库
+            let snapshot = self.counter.snapshot();
+            let changed = changed_statistics(&saved, &snapshot, _today);
+            match save_statistics(&self.db_pool, changed).await {
+                Ok(()) => saved = snapshot,
+                Err(e) => error!("failed to save statistics: {}", e),
+            }
+
+            // 检测往日流量异常
+            for (day, _, signals) in closed.iter_mut() {
+                self.detect_anomalies(*day, std::mem::take(signals)).await;
+            }
+            self.refresh_rank().await;
+            if closed.is_empty() {
+                continue;
+            }
+            // 往日数据已落库，按收盘时的排行记录名次
+            for (day, _, _) in closed.iter() {
+                self.snapshot_rank(*day).await;
+            }
+            // 更新上日访问量均值
+            let level_divisor = self.config.counter.level_divisor;
+            match with_conn(&self.db_pool, move |conn| {
+                Ok(Statistics::prev_day_rank_avg(conn, tz, level_divisor))
+            })
+            .await
+            {
+                Ok(rank_svg) => self.rank_svg.store(rank_svg, Ordering::Relaxed),
+                Err(e) => error!("failed to refresh level baseline: {}", e),
+            }
+            match load_history(&self.db_pool, tz).await {
+                Ok(history) => *self.history.write().unwrap() = history,
+                Err(e) => error!("failed to refresh history: {}", e),
+            }
+        }
+    }
+
+    /// 重新加载排行与异常标记
+    pub async fn refresh_rank(&self) {
+        match load_rank(&self.db_pool, self.config.rank.stale_days).await {
+            Ok((rank, monthly_rank)) => {
+                *self.rank.write().await = rank;
+                *self.monthly_rank.write().await = monthly_rank;
+            }
+            Err(e) => error!("failed to refresh rank: {}", e),
+        }
+        match with_conn(&self.db_pool, |conn| Anomaly::all(conn)).await {
+            Ok(anomalies) => *self.anomalies.write().await = anomalies,
+            Err(e) => error!("failed to refresh anomalies: {}", e),
+        }
+    }
+
+    /// 站点当前在月榜与总排行中的名次，及昨日收盘时的名次
+    pub async fn standing(&self, domain: &str) -> Standing {
+        let Some(id) = self.domain2id.get(domain) else {
+            return Standing::default();
+        };
+        let monthly = self.positions(&self.monthly_rank.read().await).remove(id);
+        let overall = self.positions(&self.rank.read().await).remove(id);
+        let snapshot = self.rank_snapshot.read().unwrap();
+        // 还没有快照时不标升降
+        if snapshot.is_empty() {
+            return Standing {
+                monthly,
+                monthly_prev: monthly,
+                overall,
+                overall_prev: overall,
+            };
+        }
+        let prev = snapshot.get(id);
+        Standing {
+            monthly,
+            monthly_prev: prev.and_then(|s| s.monthly_position),
+            overall,
+            overall_prev: prev.and_then(|s| s.position),
+        }
+    }
+
+    // 与排行页一致，只给未隐藏且未过期的站点排名次
+    fn positions(&self, rank: &[Statistics]) -> HashMap<i64, i64> {
+        let stale_before = now_utc() - chrono::Duration::days(self.config.rank.stale_days);
+        rank.iter()
+            .filter(|r| self.id2member.contains_key(&r.membership_id))
+            .filter(|r| r.updated_at >= stale_before)
+            .enumerate()
+            .map(|(i, r)| (r.membership_id, i as i64 + 1))
+            .collect()
+    }
+
+    /// 记录某天收盘时的名次，并作为之后比较升降的基准
+    async fn snapshot_rank(&self, day: NaiveDateTime) {
+        let monthly = self.positions(&self.monthly_rank.read().await);
+        let overall = self.positions(&self.rank.read().await);
+        let list = self
+            .id2member
+            .keys()
+            .filter(|id| monthly.contains_key(id) || overall.contains_key(id))
+            .map(|id| NewRankSnapshot {
+                membership_id: *id,
+                created_at: day,
+                position: overall.get(id).copied(),
+                monthly_position: monthly.get(id).copied(),
+            })
+            .collect::<Vec<_>>();
+        let res = with_conn(&self.db_pool, move |conn| {
+            RankSnapshot::save_all(conn, &list)?;
+            RankSnapshot::of_day(conn, day)
+        })
+        .await;
+        match res {
+            Ok(snapshot) => {
+                info!("rank snapshot of {} saved, {} members", day, snapshot.len());
+                *self.rank_snapshot.write().unwrap() =
+                    snapshot.into_iter().map(|s| (s.membership_id, s)).collect();
+            }
+            Err(e) => error!("failed to save rank snapshot: {}", e),
+        }
+    }
+
+    /// 对比自身历史与全湾分布，标记某天的异常站点
+    async fn detect_anomalies(&self, day: NaiveDateTime, signals: HashMap<i64, DaySignals>) {
+        let config = self.config.anomaly.clone();
+        if !config.enabled {
+            return;
+        }
+        let start = day - chrono::Duration::days(config.history_days);
+        let res = with_conn(&self.db_pool, move |conn| {
+            let stats = Statistics::between(conn, start, day)?;
+            let found = anomaly::detect(&config, day, &stats, &signals);
+            for item in found.iter() {
+                Anomaly::insert_or_update(conn, item)?;
+            }
+            Ok(found.len())
+        })
+        .await;
+        match res {
+            Ok(n) => info!("{} anomalies detected at {}", n, day),
+            Err(e) => error!("failed to detect anomalies: {}", e),
         }
     }
 }
+
+// 与上次写入相比有变化的站点
+fn changed_statistics(
+    saved: &HashMap<i64, MemberCount>,
+    snapshot: &HashMap<i64, MemberCount>,
+    day: NaiveDateTime,
+) -> Vec<Statistics> {
+    snapshot
+        .iter()
+        .filter(|(id, count)| {
+            let prev = saved.get(id).copied().unwrap_or_default();
+            prev.unique_visitor != count.unique_visitor
+                || prev.referrer != count.referrer
+                || prev.bot != count.bot
+                || prev.unverified != count.unverified
+        })
+        .map(|(id, count)| count.to_statistics(*id, day))
+        .collect()
+}
+
+pub async fn save_statistics(db_pool: &DbPool, list: Vec<Statistics>) -> Result<(), AppError> {
+    if list.is_empty() {
+        return Ok(());
+    }
+    with_conn(db_pool, move |conn| {
+        for stat in list.iter() {
+            Statistics::insert_or_update(conn, stat)?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+// 总排行与月榜
+async fn load_rank(
+    db_pool: &DbPool,
+    stale_days: i64,
+) -> Result<(Vec<Statistics>, Vec<Statistics>), AppError> {
+    with_conn(db_pool, move |conn| {
+        let rank = Statistics::rank_between(conn, NaiveDateTime::from_timestamp(0, 0), now_utc())?;
+        let monthly_rank = Statistics::rank_between(
+            conn,
+            now_utc() - chrono::Duration::days(stale_days),
+            now_utc(),
+        )?;
+        Ok((rank, monthly_rank))
+    })
+    .await
+}
+
+// 此前 HISTORY_DAYS 天（不含今天）逐日的 UV+RV
+async fn load_history(db_pool: &DbPool, tz: Tz) -> Result<HashMap<i64, Vec<i64>>, AppError> {
+    let today = today_start(tz);
+    let start = today - chrono::Duration::days(HISTORY_DAYS as i64);
+    let stats = with_conn(db_pool, move |conn| {
+        Statistics::between(conn, start, today - chrono::Duration::seconds(1))
+    })
+    .await?;
+    let mut history: HashMap<i64, Vec<i64>> = HashMap::new();
+    for s in stats.iter() {
+        let days_ago = days_between(today, s.created_at);
+        if !(1..=HISTORY_DAYS as i64).contains(&days_ago) {
+            continue;
+        }
+        history
+            .entry(s.membership_id)
+            .or_insert_with(|| vec![0; HISTORY_DAYS])[HISTORY_DAYS - days_ago as usize] =
+            s.unique_visitor + s.referrer;
+    }
+    Ok(history)
+}
+
+// 访客通过 DNT 或 GPC 表示不希望被追踪
+fn privacy_opted_out(headers: &HeaderMap) -> bool {
+    ["DNT", "Sec-GPC"].iter().any(|name| {
+        headers
+            .get(*name)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.trim() == "1")
+    })
+}
+
+// 日志与广播中只出现打码后的 IP
+/// 日志与实时动态中只出现打码后的 IP
+pub fn mask_ip(ip: &str) -> String {
+    IPV6_MASK
+        .replace_all(&IPV4_MASK.replace_all(ip, "$1****$2"), "$1****$2")
+        .to_string()
+}
+
+fn header_value(headers: &HeaderMap, name: &'static str) -> Result<String, AppError> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .ok_or(AppError::BadHeader(name))
+}
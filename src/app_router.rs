@@ -5,30 +5,44 @@ use askama::Template;
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        Extension, Path, WebSocketUpgrade,
+        Extension, Path, Query, WebSocketUpgrade,
     },
-    http::StatusCode,
-    response::{Headers, Html, IntoResponse, Response},
+    http::{HeaderValue, Method, StatusCode, Uri},
+    response::{Headers, Html, IntoResponse, Redirect, Response},
+    Json,
 };
 use chrono::NaiveDateTime;
-use headers::HeaderMap;
+use chrono_tz::Tz;
+use headers::{authorization::Basic, Authorization, HeaderMap, HeaderMapExt};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use tokio::select;
 
 use crate::{
-    app_model::{Context, DynContext},
-    boring_face::BoringFace,
-    membership_model::{Membership, RankAndMembership},
-    now_shanghai, GIT_HASH,
+    anomaly_model::Anomaly,
+    app_model::{Context, Decision, DynContext, VisitorType},
+    boring_face::{BoringFace, Mode, Palette, Style},
+    error::{AppError, SvgError},
+    membership_model::{AnomalyAndMembership, Membership, RankAndMembership},
+    now_utc,
+    raster::Size,
+    rate_limit::{Client, ClientIp, WsSlot},
+    with_conn, GIT_HASH,
 };
 
 pub async fn ws_upgrade(
     Extension(ctx): Extension<DynContext>,
+    ClientIp(ip): ClientIp,
     ws: WebSocketUpgrade,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(ctx, socket))
+) -> Result<Response, AppError> {
+    // 连接名额随连接结束释放
+    let slot = ctx.limiter.acquire_ws(&ip)?;
+    Ok(ws
+        .on_upgrade(|socket| handle_socket(ctx, socket, slot))
+        .into_response())
 }
 
-async fn handle_socket(ctx: Arc<Context>, mut socket: WebSocket) {
+async fn handle_socket(ctx: Arc<Context>, mut socket: WebSocket, _slot: WsSlot) {
     let mut rx = ctx.visitor_rx.clone();
     let mut interval = tokio::time::interval(Duration::from_secs(8));
 
@@ -47,122 +61,485 @@ async fn handle_socket(ctx: Arc<Context>, mut socket: WebSocket) {
                     break;
                 }
             }
+            // 及时发现断开，释放连接名额
+            msg = socket.recv() => {
+                if matches!(msg, None | Some(Err(_)) | Some(Ok(Message::Close(_)))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BadgeQuery {
+    /// 签名的嵌入令牌
+    t: Option<String>,
+    /// 预览模式，只返回判定过程不计数
+    explain: Option<String>,
+    /// 内置配色名称
+    theme: Option<String>,
+    /// light 或 dark，不传时跟随系统
+    mode: Option<String>,
+    /// 徽章版式：badge、flat、pill、card、spark、rank
+    style: Option<String>,
+    /// spark 版式的天数，7（默认）或 30
+    days: Option<usize>,
+    /// PNG 的放大倍数，默认 1
+    scale: Option<f32>,
+    /// 正方形 PNG 的边长，用于触屏图标，优先于 scale
+    size: Option<u32>,
+}
+
+impl BadgeQuery {
+    // `?explain=1` 预览不计数，请求头 `X-Boringbay-Explain: 1` 照常计数并返回判定过程
+    fn decision(&self, headers: &HeaderMap) -> Decision {
+        let on = |v: &str| matches!(v, "1" | "true");
+        Decision::new(
+            headers
+                .get("X-Boringbay-Explain")
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(on),
+            self.explain.as_deref().is_some_and(on),
+        )
+    }
+
+    // PNG 与 ico 无法跟随系统切换日夜间，未指定时按日间渲染
+    async fn face(
+        &self,
+        ctx: &Context,
+        domain: &str,
+        face: &BoringFace,
+        inverse: bool,
+        format: Format,
+    ) -> BoringFace {
+        let palette = ctx.palette(domain, self.theme.as_deref());
+        let mode = match Mode::parse(self.mode.as_deref()) {
+            Mode::Auto if format != Format::Svg => Mode::Light,
+            mode => mode,
+        };
+        let face = face.themed(
+            match inverse {
+                true => palette.map(Palette::inverse),
+                false => palette,
+            },
+            mode,
+            Style::parse(self.style.as_deref()),
+        );
+        match face.style() {
+            Style::Spark => {
+                let days = match self.days {
+                    Some(30) => 30,
+                    _ => 7,
+                };
+                face.with_series(ctx.series(domain, days))
+            }
+            Style::Rank => face.with_standing(ctx.standing(domain).await),
+            _ => face,
+        }
+    }
+
+    async fn render(
+        &self,
+        ctx: &Context,
+        domain: &str,
+        tend: (&str, i64, i64, i64),
+        face: &BoringFace,
+        inverse: bool,
+        format: Format,
+    ) -> Response {
+        let face = self.face(ctx, domain, face, inverse, format).await;
+        self.draw(ctx, domain, tend, &face, format).await
+    }
+
+    // 绘制已按参数取好配色的徽章
+    async fn draw(
+        &self,
+        ctx: &Context,
+        domain: &str,
+        tend: (&str, i64, i64, i64),
+        face: &BoringFace,
+        format: Format,
+    ) -> Response {
+        match format {
+            Format::Svg => render_svg(tend, face).await,
+            Format::Png => render_raster(ctx, domain, tend, face, Some(self.size())).await,
+            Format::Ico => render_raster(ctx, domain, tend, face, None).await,
+        }
+    }
+
+    fn size(&self) -> Size {
+        match self.size {
+            Some(size) => Size::Square(size),
+            None => Size::Scale(self.scale.unwrap_or(1.0)),
+        }
+        .normalized()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Svg,
+    Png,
+    Ico,
+}
+
+// 路径以 `.png` 结尾时返回 PNG，图标还可以 `.ico` 结尾返回多尺寸 ico
+fn split_format(domain: String, ico: bool) -> (String, Format) {
+    if let Some(d) = domain.strip_suffix(".png") {
+        (d.to_string(), Format::Png)
+    } else if let Some(d) = domain.strip_suffix(".ico").filter(|_| ico) {
+        (d.to_string(), Format::Ico)
+    } else {
+        (domain, Format::Svg)
+    }
+}
+
+// 徽章与信标只为来源页所在的站点计数，路径为 `[domain]` 时取来源页的域名
+fn badge_target(
+    domain: String,
+    headers: &HeaderMap,
+    decision: &mut Decision,
+) -> (String, Option<VisitorType>) {
+    let domain_referrer = get_domain_from_referrer(headers).unwrap_or("".to_string());
+    if domain_referrer.eq(&domain) {
+        decision.step(|| "referrer matches");
+        (domain, Some(VisitorType::Badge))
+    } else if domain.eq("[domain]") {
+        decision.step(|| format!("domain from referrer {}", domain_referrer));
+        (domain_referrer, Some(VisitorType::Badge))
+    } else {
+        decision.step(|| {
+            if domain_referrer.is_empty() {
+                "no referrer, display only".to_string()
+            } else {
+                format!("referrer {} does not match, display only", domain_referrer)
+            }
+        });
+        (domain, None)
+    }
+}
+
+// explain 模式下附上判定过程，且响应不可缓存
+fn explained(mut res: Response, decision: &Decision) -> Response {
+    if decision.explain {
+        let res_headers = res.headers_mut();
+        if let Ok(v) = HeaderValue::from_str(&decision.to_header()) {
+            res_headers.insert("x-boringbay-decision", v);
         }
+        res_headers.insert("cache-control", HeaderValue::from_static("no-store"));
     }
+    res
 }
 
 pub async fn show_badge(
-    Path(mut domain): Path<String>,
+    Path(domain): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    client: Client,
     headers: HeaderMap,
     Extension(ctx): Extension<DynContext>,
 ) -> Response {
-    let mut v_type = Some(crate::app_model::VisitorType::Badge);
-
-    let domain_referrer = get_domain_from_referrer(&headers).unwrap_or("".to_string());
-    if domain_referrer.ne(&domain) {
-        if domain.eq("[domain]") {
-            domain = domain_referrer;
-        } else {
-            v_type = None;
+    let mut decision = query.decision(&headers);
+    let (domain, format) = split_format(domain, false);
+    let (domain, v_type) = badge_target(domain, &headers, &mut decision);
+    let res = match ctx
+        .boring_visitor(
+            v_type,
+            &domain,
+            &headers,
+            &client,
+            query.t.as_deref(),
+            &mut decision,
+        )
+        .await
+    {
+        Ok(tend) => {
+            query
+                .render(&ctx, &domain, tend, &ctx.badge, false, format)
+                .await
         }
-    }
+        Err(e) => SvgError(e).into_response(),
+    };
+    explained(res, &decision)
+}
+
+// 透明的 1x1 GIF
+const PIXEL_GIF: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
 
-    let tend = ctx.boring_visitor(v_type, &domain, &headers).await;
-    if tend.is_err() {
-        return (
-            StatusCode::NOT_FOUND,
-            Headers([("content-type", "text/plain")]),
-            tend.err().unwrap().to_string(),
+/// 只计数不渲染的信标，GET 返回 1x1 GIF，POST 供 `navigator.sendBeacon` 使用
+pub async fn beacon(
+    method: Method,
+    Path(domain): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    client: Client,
+    headers: HeaderMap,
+    Extension(ctx): Extension<DynContext>,
+) -> Response {
+    let mut decision = query.decision(&headers);
+    let (domain, v_type) = badge_target(domain, &headers, &mut decision);
+    let res = match ctx
+        .boring_visitor(
+            v_type,
+            &domain,
+            &headers,
+            &client,
+            query.t.as_deref(),
+            &mut decision,
         )
-            .into_response();
-    }
+        .await
+    {
+        Ok(_) if method == Method::POST => StatusCode::NO_CONTENT.into_response(),
+        Ok(_) => (
+            StatusCode::OK,
+            Headers([("content-type", "image/gif"), ("cache-control", "no-store")]),
+            PIXEL_GIF.to_vec(),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    };
+    explained(res, &decision)
+}
 
-    render_svg(tend.unwrap(), &ctx.badge).await
+/// 只渲染不计数的徽章，可被浏览器与 CDN 缓存，数字未变时返回 304
+pub async fn render_badge(
+    Path(domain): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    headers: HeaderMap,
+    Extension(ctx): Extension<DynContext>,
+) -> Result<Response, SvgError> {
+    let (domain, format) = split_format(domain, false);
+    let tend = ctx.peek(&domain)?;
+    // 配色随品牌色与参数变化，一并计入 ETag
+    let face = query.face(&ctx, &domain, &ctx.badge, false, format).await;
+    let etag = format!(
+        "\"{}\"",
+        &hex::encode(Sha256::digest(
+            format!(
+                "{}:{}:{}:{}:{}:{:?}:{:?}",
+                GIT_HASH,
+                tend.0,
+                tend.1,
+                tend.2,
+                tend.3,
+                face,
+                (format == Format::Png).then(|| query.size())
+            )
+            .as_bytes()
+        ))[..16]
+    );
+    let cache_control = format!("public, max-age={}", ctx.config.badge.render_max_age_secs);
+    let not_modified = headers
+        .get("If-None-Match")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+    let mut res = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        query.draw(&ctx, &domain, tend, &face, format).await
+    };
+    let res_headers = res.headers_mut();
+    res_headers.insert("etag", HeaderValue::from_str(&etag).unwrap());
+    res_headers.insert(
+        "cache-control",
+        HeaderValue::from_str(&cache_control).unwrap(),
+    );
+    Ok(res)
 }
 
 pub async fn show_favicon(
     Path(domain): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    client: Client,
     headers: HeaderMap,
     Extension(ctx): Extension<DynContext>,
 ) -> Response {
-    let tend = ctx
-        .boring_visitor(Some(crate::app_model::VisitorType::ICON), &domain, &headers)
-        .await;
-    if tend.is_err() {
-        return (
-            StatusCode::NOT_FOUND,
-            Headers([("content-type", "text/plain")]),
-            tend.err().unwrap().to_string(),
+    let mut decision = query.decision(&headers);
+    let (domain, format) = split_format(domain, true);
+    let res = match ctx
+        .boring_visitor(
+            Some(crate::app_model::VisitorType::ICON),
+            &domain,
+            &headers,
+            &client,
+            None,
+            &mut decision,
         )
-            .into_response();
-    }
-    render_svg(tend.unwrap(), &ctx.favicon).await
+        .await
+    {
+        Ok(tend) => {
+            query
+                .render(&ctx, &domain, tend, &ctx.favicon, true, format)
+                .await
+        }
+        Err(e) => SvgError(e).into_response(),
+    };
+    explained(res, &decision)
 }
 
 pub async fn show_icon(
     Path(domain): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    client: Client,
     headers: HeaderMap,
     Extension(ctx): Extension<DynContext>,
 ) -> Response {
-    let tend = ctx
-        .boring_visitor(Some(crate::app_model::VisitorType::ICON), &domain, &headers)
-        .await;
-    if tend.is_err() {
-        return (
-            StatusCode::NOT_FOUND,
-            Headers([("content-type", "text/plain")]),
-            tend.err().unwrap().to_string(),
+    let mut decision = query.decision(&headers);
+    let (domain, format) = split_format(domain, true);
+    let res = match ctx
+        .boring_visitor(
+            Some(crate::app_model::VisitorType::ICON),
+            &domain,
+            &headers,
+            &client,
+            None,
+            &mut decision,
         )
-            .into_response();
+        .await
+    {
+        Ok(tend) => {
+            query
+                .render(&ctx, &domain, tend, &ctx.icon, false, format)
+                .await
+        }
+        Err(e) => SvgError(e).into_response(),
+    };
+    explained(res, &decision)
+}
+
+/// 成员站点的 web app manifest，图标均取自 favicon，配色随 theme 参数与品牌色
+pub async fn show_manifest(
+    Path(domain): Path<String>,
+    Query(query): Query<BadgeQuery>,
+    Extension(ctx): Extension<DynContext>,
+) -> Result<Response, AppError> {
+    let domain = domain
+        .strip_suffix(".webmanifest")
+        .unwrap_or(&domain)
+        .to_string();
+    let member = ctx
+        .domain2id
+        .get(&domain)
+        .and_then(|id| ctx.id2member.get(id))
+        .ok_or(AppError::NotMember)?;
+    let face = query
+        .face(&ctx, &domain, &ctx.favicon, true, Format::Png)
+        .await;
+    let (fill_white, fill_black) = face.colors();
+    // 只传递内置配色名，无需转义
+    let theme = query
+        .theme
+        .as_deref()
+        .filter(|t| Palette::named(t).is_some())
+        .map(|t| format!("theme={}&", t))
+        .unwrap_or_default();
+    let icon = |ext: &str| format!("/api/favicon/{}.{}?{}", domain, ext, theme);
+    let manifest = serde_json::json!({
+        "name": member.name,
+        "short_name": member.name,
+        "description": member.description,
+        "start_url": format!("https://{}/", domain),
+        "display": "browser",
+        "theme_color": fill_black,
+        "background_color": fill_white,
+        "icons": [
+            { "src": icon("ico").trim_end_matches(['?', '&']), "sizes": "16x16 32x32 48x48", "type": "image/x-icon" },
+            { "src": format!("{}size=192", icon("png")), "sizes": "192x192", "type": "image/png" },
+            { "src": format!("{}size=512", icon("png")), "sizes": "512x512", "type": "image/png" },
+        ],
+    });
+    Ok((
+        StatusCode::OK,
+        Headers([
+            ("content-type", "application/manifest+json"),
+            ("access-control-allow-origin", "*"),
+            ("cache-control", "public, max-age=86400"),
+        ]),
+        manifest.to_string(),
+    )
+        .into_response())
+}
+
+// 模板中的时间均为 UTC，展示时转为本地时区
+mod filters {
+    use chrono::NaiveDateTime;
+    use chrono_tz::Tz;
+
+    pub fn local(t: &NaiveDateTime, tz: &Tz) -> askama::Result<String> {
+        Ok(crate::to_local(*tz, t)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string())
     }
 
-    render_svg(tend.unwrap(), &ctx.icon).await
+    pub fn local_date(t: &NaiveDateTime, tz: &Tz) -> askama::Result<String> {
+        Ok(crate::to_local(*tz, t).format("%Y-%m-%d").to_string())
+    }
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
 struct HomeTemplate {
     version: String,
+    tz: Tz,
+    stale_days: i64,
     membership: Vec<Membership>,
     uv: HashMap<i64, i64>,
     referrer: HashMap<i64, i64>,
+    bot: HashMap<i64, i64>,
+    unverified: HashMap<i64, i64>,
     rank: Vec<RankAndMembership>,
     to_be_remove: Vec<RankAndMembership>,
     level: HashMap<i64, i64>,
 }
 
+#[derive(Deserialize)]
+pub struct PageQuery {
+    /// 来源站点，由 embed.js 附加在链接上
+    from: Option<String>,
+}
+
+// 优先取 Referer，来源页不发送 Referer 时退回 from 参数
+fn referrer_domain(headers: &HeaderMap, from: Option<String>) -> Option<String> {
+    get_domain_from_referrer(headers).ok().or_else(|| {
+        from.map(|f| f.trim().to_lowercase())
+            .filter(|f| !f.is_empty())
+    })
+}
+
 pub async fn home_page(
     Extension(ctx): Extension<DynContext>,
+    Query(query): Query<PageQuery>,
+    client: Client,
     headers: HeaderMap,
-) -> Result<Html<String>, String> {
-    let domain = get_domain_from_referrer(&headers);
-    if domain.is_ok() {
+) -> Result<Html<String>, AppError> {
+    if let Some(domain) = referrer_domain(&headers, query.from) {
         let _ = ctx
             .boring_visitor(
                 Some(crate::app_model::VisitorType::Referer),
-                &domain.unwrap(),
+                &domain,
                 &headers,
+                &client,
+                None,
+                &mut Decision::default(),
             )
             .await;
     }
-    let referrer_read = ctx.referrer.read().await;
-    let uv_read = ctx.unique_visitor.read().await;
+    // 渲染只用计数快照，不阻塞计数
+    let counts = ctx.counter.snapshot();
 
     let mut level: HashMap<i64, i64> = HashMap::new();
     let mut rank_vec: Vec<(i64, NaiveDateTime, i64)> = Vec::new();
 
-    for k in ctx.id2member.keys() {
-        let uv = uv_read
-            .get(k)
-            .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)))
-            .to_owned();
-        let rv = referrer_read
-            .get(k)
-            .unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)))
-            .to_owned();
-        if uv.0 > 0 || rv.0 > 0 {
-            rank_vec.push((k.to_owned(), rv.1, uv.0));
-            level.insert(k.to_owned(), ctx.get_tend_from_uv_and_rv(uv.0, rv.0).await);
+    for (k, c) in counts.iter() {
+        if c.unique_visitor > 0 || c.referrer > 0 {
+            rank_vec.push((k.to_owned(), c.latest_referrer_at, c.unique_visitor));
+            level.insert(
+                k.to_owned(),
+                ctx.get_tend_from_uv_and_rv(c.unique_visitor, c.referrer),
+            );
         }
     }
 
@@ -173,19 +550,25 @@ pub async fn home_page(
 
     let mut membership = Vec::new();
     for v in rank_vec {
-        membership.push(ctx.id2member.get(&v.0).unwrap().to_owned());
+        membership.push(
+            ctx.id2member
+                .get(&v.0)
+                .ok_or(AppError::NotMember)?
+                .to_owned(),
+        );
     }
 
     let mut rank_and_membership_to_be_remove = Vec::new();
     let mut rank_and_membership = Vec::new();
 
+    let stale_before = now_utc() - chrono::Duration::days(ctx.config.rank.stale_days);
     let monthly_rank = ctx.monthly_rank.read().await.to_owned();
     monthly_rank
         .iter()
         .filter(|r| ctx.id2member.contains_key(&r.membership_id))
         .for_each(|r| {
-            if rank_and_membership.len() >= 10
-                || r.updated_at < now_shanghai() - chrono::Duration::days(30)
+            if rank_and_membership.len() >= ctx.config.rank.homepage_limit
+                || r.updated_at < stale_before
             {
                 return;
             }
@@ -200,7 +583,7 @@ pub async fn home_page(
     rank.iter()
         .filter(|r| ctx.id2member.contains_key(&r.membership_id))
         .for_each(|r| {
-            if r.updated_at < now_shanghai() - chrono::Duration::days(30) {
+            if r.updated_at < stale_before {
                 let m = ctx.id2member.get(&r.membership_id).unwrap().to_owned();
                 rank_and_membership_to_be_remove.push(RankAndMembership {
                     rank: r.to_owned(),
@@ -211,20 +594,30 @@ pub async fn home_page(
 
     let tpl = HomeTemplate {
         membership,
-        uv: uv_read
+        uv: counts
+            .iter()
+            .map(|(k, v)| (k.to_owned(), v.unique_visitor))
+            .collect::<HashMap<i64, i64>>(),
+        referrer: counts
+            .iter()
+            .map(|(k, v)| (k.to_owned(), v.referrer))
+            .collect::<HashMap<i64, i64>>(),
+        bot: counts
             .iter()
-            .map(|(k, v)| (k.to_owned(), v.0))
+            .map(|(k, v)| (k.to_owned(), v.bot))
             .collect::<HashMap<i64, i64>>(),
-        referrer: referrer_read
+        unverified: counts
             .iter()
-            .map(|(k, v)| (k.to_owned(), v.0))
+            .map(|(k, v)| (k.to_owned(), v.unverified))
             .collect::<HashMap<i64, i64>>(),
         rank: rank_and_membership,
         to_be_remove: rank_and_membership_to_be_remove,
         level,
         version: GIT_HASH[0..8].to_string(),
+        tz: ctx.config.server.timezone,
+        stale_days: ctx.config.rank.stale_days,
     };
-    let html = tpl.render().map_err(|err| err.to_string())?;
+    let html = tpl.render()?;
     Ok(Html(html))
 }
 
@@ -234,11 +627,11 @@ struct JoinUsTemplate {
     version: String,
 }
 
-pub async fn join_us_page() -> Result<Html<String>, String> {
+pub async fn join_us_page() -> Result<Html<String>, AppError> {
     let tpl = JoinUsTemplate {
         version: GIT_HASH[0..8].to_string(),
     };
-    let html = tpl.render().map_err(|err| err.to_string())?;
+    let html = tpl.render()?;
     Ok(Html(html))
 }
 
@@ -246,26 +639,33 @@ pub async fn join_us_page() -> Result<Html<String>, String> {
 #[template(path = "rank.html")]
 struct RankTemplate {
     version: String,
+    tz: Tz,
     rank: Vec<RankAndMembership>,
     to_be_remove: Vec<RankAndMembership>,
+    flagged: HashMap<i64, usize>,
 }
 
 pub async fn rank_page(
     Extension(ctx): Extension<DynContext>,
+    Query(query): Query<PageQuery>,
+    client: Client,
     headers: HeaderMap,
-) -> Result<Html<String>, String> {
-    let domain = get_domain_from_referrer(&headers);
-    if domain.is_ok() {
+) -> Result<Html<String>, AppError> {
+    if let Some(domain) = referrer_domain(&headers, query.from) {
         let _ = ctx
             .boring_visitor(
                 Some(crate::app_model::VisitorType::Referer),
-                &domain.unwrap(),
+                &domain,
                 &headers,
+                &client,
+                None,
+                &mut Decision::default(),
             )
             .await;
     }
 
     let rank = ctx.rank.read().await.to_owned();
+    let stale_before = now_utc() - chrono::Duration::days(ctx.config.rank.stale_days);
 
     let mut rank_and_membership_to_be_remove = Vec::new();
 
@@ -274,7 +674,7 @@ pub async fn rank_page(
     rank.iter()
         .filter(|r| ctx.id2member.contains_key(&r.membership_id))
         .for_each(|r| {
-            if r.updated_at > now_shanghai() - chrono::Duration::days(30) {
+            if r.updated_at > stale_before {
                 let m = ctx.id2member.get(&r.membership_id).unwrap().to_owned();
                 rank_and_membership.push(RankAndMembership {
                     rank: r.to_owned(),
@@ -289,15 +689,161 @@ pub async fn rank_page(
             }
         });
 
+    // 未被排除的异常天数，排行旁提示
+    let mut flagged: HashMap<i64, usize> = HashMap::new();
+    ctx.anomalies
+        .read()
+        .await
+        .iter()
+        .filter(|a| !a.excluded)
+        .for_each(|a| *flagged.entry(a.membership_id).or_default() += 1);
+
     let tpl = RankTemplate {
         rank: rank_and_membership,
         to_be_remove: rank_and_membership_to_be_remove,
+        flagged,
+        version: GIT_HASH[0..8].to_string(),
+        tz: ctx.config.server.timezone,
+    };
+    let html = tpl.render()?;
+    Ok(Html(html))
+}
+
+#[derive(Template)]
+#[template(path = "admin_anomalies.html")]
+struct AdminAnomaliesTemplate {
+    version: String,
+    tz: Tz,
+    anomalies: Vec<AnomalyAndMembership>,
+}
+
+pub async fn admin_anomalies_page(
+    Extension(ctx): Extension<DynContext>,
+    headers: HeaderMap,
+) -> Result<Html<String>, AppError> {
+    check_admin(&ctx, &headers)?;
+
+    let anomalies = ctx
+        .anomalies
+        .read()
+        .await
+        .iter()
+        .filter_map(|a| {
+            ctx.id2member
+                .get(&a.membership_id)
+                .map(|m| AnomalyAndMembership {
+                    anomaly: a.to_owned(),
+                    membership: m.to_owned(),
+                })
+        })
+        .collect();
+
+    let tpl = AdminAnomaliesTemplate {
         version: GIT_HASH[0..8].to_string(),
+        tz: ctx.config.server.timezone,
+        anomalies,
     };
-    let html = tpl.render().map_err(|err| err.to_string())?;
+    let html = tpl.render()?;
     Ok(Html(html))
 }
 
+pub async fn exclude_anomaly(
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Extension(ctx): Extension<DynContext>,
+) -> Result<Redirect, AppError> {
+    set_anomaly_excluded(&ctx, &headers, id, true).await
+}
+
+pub async fn include_anomaly(
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+    Extension(ctx): Extension<DynContext>,
+) -> Result<Redirect, AppError> {
+    set_anomaly_excluded(&ctx, &headers, id, false).await
+}
+
+async fn set_anomaly_excluded(
+    ctx: &Context,
+    headers: &HeaderMap,
+    id: i32,
+    excluded: bool,
+) -> Result<Redirect, AppError> {
+    check_admin(ctx, headers)?;
+    check_same_origin(ctx, headers)?;
+    with_conn(&ctx.db_pool, move |conn| {
+        Anomaly::set_excluded(conn, id, excluded)
+    })
+    .await?;
+    tracing::info!("anomaly {} excluded: {}", id, excluded);
+    // 排除与恢复立即反映到排行
+    ctx.refresh_rank().await;
+    Ok(Redirect::to(Uri::from_static("/admin/anomalies")))
+}
+
+pub async fn reload_access(
+    headers: HeaderMap,
+    Extension(ctx): Extension<DynContext>,
+) -> Result<Response, AppError> {
+    check_admin(&ctx, &headers)?;
+    check_same_origin(&ctx, &headers)?;
+    Ok(match ctx.reload_access() {
+        Ok(rules) => Json(serde_json::json!({ "rules": rules })).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": format!("{:#}", e) })),
+        )
+            .into_response(),
+    })
+}
+
+// 管理后台使用 HTTP Basic 认证，比较摘要以免逐字节比较泄露长度信息
+fn check_admin(ctx: &Context, headers: &HeaderMap) -> Result<(), AppError> {
+    let password = &ctx.config.admin.password;
+    if password.is_empty() {
+        return Err(AppError::AdminDisabled);
+    }
+    let auth = headers
+        .typed_get::<Authorization<Basic>>()
+        .ok_or(AppError::Unauthorized)?;
+    if auth.0.username() != "admin" || Sha256::digest(auth.0.password()) != Sha256::digest(password)
+    {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(())
+}
+
+// 浏览器会自动附带 Basic 认证，修改数据的请求须来自本站页面以防 CSRF。
+// 跨站的 POST 必带 Origin，没有 Origin 时看 Referer；两者都没有的是 curl 等非浏览器请求，放行
+fn check_same_origin(ctx: &Context, headers: &HeaderMap) -> Result<(), AppError> {
+    let Some(source) = ["Origin", "Referer"]
+        .iter()
+        .find_map(|name| headers.get(*name).and_then(|v| v.to_str().ok()))
+    else {
+        return Ok(());
+    };
+    let source = url::Url::parse(source).map_err(|_| AppError::CrossOrigin)?;
+    let source = match source.port() {
+        Some(port) => format!("{}:{}", source.host_str().unwrap_or_default(), port),
+        None => source.host_str().unwrap_or_default().to_string(),
+    };
+    let host = headers.get("Host").and_then(|v| v.to_str().ok());
+    if source == ctx.config.server.system_domain || Some(source.as_str()) == host {
+        return Ok(());
+    }
+    Err(AppError::CrossOrigin)
+}
+
+pub async fn embed_js() -> impl IntoResponse {
+    (
+        Headers([
+            ("content-type", "application/javascript; charset=utf-8"),
+            ("cache-control", "public, max-age=86400"),
+        ]),
+        include_str!("../resources/embed.js"),
+    )
+}
+
 fn get_domain_from_referrer(headers: &HeaderMap) -> Result<String, anyhow::Error> {
     let referrer_header = headers.get("Referer");
     if referrer_header.is_none() {
@@ -319,7 +865,32 @@ fn get_domain_from_referrer(headers: &HeaderMap) -> Result<String, anyhow::Error
         return Err(anyhow!("referrer header doesn't contains a valid domain"));
     }
 
-    return Ok(referrer_url.domain().unwrap().to_string());
+    Ok(referrer_url.domain().unwrap().to_string())
+}
+
+// 按站点、数字与配色版式缓存，命中时不再渲染；不指定尺寸时输出多尺寸 ico
+async fn render_raster(
+    ctx: &Context,
+    domain: &str,
+    tend: (&str, i64, i64, i64),
+    face: &BoringFace,
+    size: Option<Size>,
+) -> Response {
+    let key = format!("{}:{}:{}:{}:{:?}", domain, tend.1, tend.2, tend.3, face);
+    let svg = || face.render_svg(tend.0, tend.1, tend.2, tend.3);
+    let (content_type, res) = match size {
+        Some(size) => ("image/png", ctx.raster.render_png(key, svg, size).await),
+        None => ("image/x-icon", ctx.raster.render_ico(key, svg).await),
+    };
+    match res {
+        Ok(image) => (
+            StatusCode::OK,
+            Headers([("content-type", content_type)]),
+            image.to_vec(),
+        )
+            .into_response(),
+        Err(e) => SvgError(e).into_response(),
+    }
 }
 
 async fn render_svg(tend: (&str, i64, i64, i64), render: &BoringFace) -> Response {
@@ -1,7 +1,7 @@
-static SVG_HEADER: &str = r###"<?xml version="1.0" encoding="utf-8"?>
-<svg #svg_viewport# xmlns="http://www.w3.org/2000/svg">
-  <defs>
-    <style>
+use crate::svg::Element;
+use crate::text_metrics::{fit, text_width};
+
+static SVG_STYLE: &str = r###"
         text {
             fill: #fill_black#;
             font-weight: 900 !important;
@@ -13,6 +13,15 @@ static SVG_HEADER: &str = r###"<?xml version="1.0" encoding="utf-8"?>
         .fill-white {
             fill: #fill_white#
         }
+        .fill-empty {
+            fill: #fill_empty#
+        }
+        .border {
+            stroke: #stroke#;
+            stroke-width: 5
+        }#dark_media#
+"###;
+static SVG_DARK_MEDIA: &str = r###"
         @media (prefers-color-scheme: dark) {
             text {
                 fill: #fill_white#
@@ -23,32 +32,191 @@ static SVG_HEADER: &str = r###"<?xml version="1.0" encoding="utf-8"?>
             .fill-white {
                 fill: #fill_black#
             }
+            .border {
+                stroke: #stroke_dark#
+            }
+        }"###;
+
+// 默认的未点亮牙齿颜色
+static FILL_EMPTY: &str = "#d55f6f";
+
+// 内置配色：名称、主色、浅色
+static PALETTES: [(&str, &str, &str); 6] = [
+    ("rmb", "#d0273e", "#f5acb9"),
+    ("jade", "#1f7a5a", "#a8dcc4"),
+    ("ocean", "#1d5fa8", "#a9cdf2"),
+    ("violet", "#6b3fa0", "#d4c2ec"),
+    ("amber", "#b86e00", "#f6d49b"),
+    ("mono", "#24292f", "#d0d7de"),
+];
+
+/// 配色模式，auto 跟随 `prefers-color-scheme`，light 与 dark 固定配色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+impl Mode {
+    /// 无法识别的取值按 auto 处理
+    pub fn parse(mode: Option<&str>) -> Self {
+        match mode {
+            Some("light") => Mode::Light,
+            Some("dark") => Mode::Dark,
+            _ => Mode::Auto,
         }
-    </style>
-  </defs>
-  #svg_border#
-  <g transform="matrix(1, 0, 0, 1, 1.062999, 0.155999)" fill="none" fill-rule="evenodd">
-    <circle class="fill-black" cx="54.5" cy="54.5" r="48.5"/>
-    <g class="fill-white" transform="translate(19.587 30.779)">
-      <circle cx="14.861" cy="13.861" r="8.861"/>
-      <circle cx="54.966" cy="13.861" r="8.861"/>
-    </g>
-    <rect class="fill-white" x="31.5" y="71.2" width="47" height="8.529" rx="5"/>
-"###;
-static SVG_BORDER: &str = r###"
-<rect class="fill-white" width="577.762" height="110" style="stroke: rgb(189, 14, 43); stroke-width: 5;" rx="50"/>
-<text style="font-size: 38px; white-space: pre; text-anchor: middle;" x="335" y="48.074">#site_name# 🥱</text>
-<text style="font-size: 24px; white-space: pre; font-style: italic; text-anchor: middle;" x="335" y="91.615">UV: #site_uv#  Referer: #site_referrer#  Level: #site_rank#</text>
-"###;
-static SVG_FOOTER: &str = r###"
-    </g>
-  
-</svg>"###;
+    }
+}
+
+/// 一套图标配色
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub fill_white: String,
+    pub fill_black: String,
+    pub fill_empty: String,
+}
+
+impl Palette {
+    /// 内置配色
+    pub fn named(name: &str) -> Option<Palette> {
+        PALETTES
+            .iter()
+            .find(|(n, _, _)| n.eq_ignore_ascii_case(name))
+            .and_then(|(_, main, light)| {
+                Some(Palette {
+                    fill_white: main.to_string(),
+                    fill_black: light.to_string(),
+                    fill_empty: mix(parse_hex(main)?, parse_hex(light)?, 0.5),
+                })
+            })
+    }
+
+    /// 由站点品牌色（`#rgb` 或 `#rrggbb`）生成配色，浅色与未点亮的牙齿由品牌色与白色混合而来
+    pub fn from_brand(color: &str) -> Option<Palette> {
+        let rgb = parse_hex(color)?;
+        let white = [0xff; 3];
+        Some(Palette {
+            fill_white: to_hex(rgb),
+            fill_black: mix(rgb, white, 0.65),
+            fill_empty: mix(rgb, white, 0.3),
+        })
+    }
+
+    /// 反色，用于 favicon
+    pub fn inverse(self) -> Palette {
+        Palette {
+            fill_white: self.fill_black,
+            fill_black: self.fill_white,
+            fill_empty: self.fill_empty,
+        }
+    }
+}
 
+fn parse_hex(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match hex.len() {
+        3 => Some([
+            channel(&hex[0..1].repeat(2))?,
+            channel(&hex[1..2].repeat(2))?,
+            channel(&hex[2..3].repeat(2))?,
+        ]),
+        6 => Some([
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        ]),
+        _ => None,
+    }
+}
+
+// 按比例 ratio 向 to 混合
+fn mix(from: [u8; 3], to: [u8; 3], ratio: f64) -> String {
+    let c = |i: usize| (from[i] as f64 + (to[i] as f64 - from[i] as f64) * ratio).round() as u8;
+    to_hex([c(0), c(1), c(2)])
+}
+
+// 边框比底色略深
+fn border(fill: &str) -> String {
+    parse_hex(fill)
+        .map(|rgb| mix(rgb, [0; 3], 0.1))
+        .unwrap_or_else(|| fill.to_string())
+}
+
+fn to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+/// 徽章版式，`?style=` 选择，无法识别的取值使用默认的带边框徽章
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// 只有脸，用于 icon 与 favicon
+    Face,
+    /// 带边框的宽徽章，展示站名、UV、RV 与等级
+    Badge,
+    /// shields.io 风格的双段徽章
+    Flat,
+    /// 只有脸与等级的胶囊
+    Pill,
+    /// 方形卡片
+    Card,
+    /// 脸与近几日 UV+RV 的柱状图
+    Spark,
+    /// 月榜与总排行的名次
+    Rank,
+}
+
+impl Style {
+    pub fn parse(style: Option<&str>) -> Option<Self> {
+        match style? {
+            "badge" => Some(Style::Badge),
+            "flat" => Some(Style::Flat),
+            "pill" => Some(Style::Pill),
+            "card" => Some(Style::Card),
+            "spark" => Some(Style::Spark),
+            "rank" => Some(Style::Rank),
+            _ => None,
+        }
+    }
+}
+
+// 带边框徽章的文字区从脸的右侧开始，默认宽 450，文字左右各留 20
+const BADGE_TEXT_LEFT: f64 = 110.0;
+const BADGE_TEXT_AREA: f64 = 450.0;
+const BADGE_RIGHT_PADDING: f64 = 17.762;
+// 卡片默认宽 220，文字左右各留 10
+const CARD_WIDTH: f64 = 220.0;
+// 柱状图宽 280，柱高最多 72
+const SPARK_WIDTH: f64 = 280.0;
+const SPARK_HEIGHT: f64 = 72.0;
+
+fn px(v: f64) -> String {
+    format!("{:.1}", v)
+}
+
+/// rank 版式展示的名次，从 1 开始，未上榜为空；prev 为昨日收盘时的名次
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Standing {
+    pub monthly: Option<i64>,
+    pub monthly_prev: Option<i64>,
+    pub overall: Option<i64>,
+    pub overall_prev: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
 pub struct BoringFace {
     fill_white: String,
     fill_black: String,
-    with_border: bool,
+    fill_empty: String,
+    style: Style,
+    mode: Mode,
+    series: Vec<i64>,
+    standing: Standing,
 }
 
 impl BoringFace {
@@ -56,48 +224,431 @@ impl BoringFace {
         Self {
             fill_white,
             fill_black,
-            with_border,
+            fill_empty: FILL_EMPTY.to_string(),
+            style: match with_border {
+                true => Style::Badge,
+                false => Style::Face,
+            },
+            mode: Mode::Auto,
+            series: Vec::new(),
+            standing: Standing::default(),
         }
     }
 
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    /// spark 版式的逐日 UV+RV，由旧到新，其他版式忽略
+    pub fn with_series(mut self, series: Vec<i64>) -> Self {
+        if self.style == Style::Spark {
+            self.series = series;
+        }
+        self
+    }
+
+    /// rank 版式的名次，其他版式忽略
+    pub fn with_standing(mut self, standing: Standing) -> Self {
+        if self.style == Style::Rank {
+            self.standing = standing;
+        }
+        self
+    }
+
+    /// 渲染时按请求换用配色、模式与版式，palette 为空时保留原配色；
+    /// 只有脸的图标不换版式
+    pub fn themed(&self, palette: Option<Palette>, mode: Mode, style: Option<Style>) -> BoringFace {
+        let mut face = self.clone();
+        if let Some(p) = palette {
+            face.fill_white = p.fill_white;
+            face.fill_black = p.fill_black;
+            face.fill_empty = p.fill_empty;
+        }
+        face.mode = mode;
+        if let Some(s) = style.filter(|_| self.style != Style::Face) {
+            face.style = s;
+        }
+        face
+    }
+
     pub fn render_svg(&self, name: &str, uv: i64, rv: i64, rank: i64) -> String {
-        assert!((1..=10).contains(&rank));
-        let mut content = SVG_HEADER
-            .replace("#fill_white#", &self.fill_white)
-            .replace("#fill_black#", &self.fill_black);
-
-        for i in 0..10 {
-            content.push_str(&format!(
-                "<rect {} x=\"{}\" y=\"72.5\" width=\"3\" height=\"6\" rx=\"1\" />",
-                match i.lt(&rank) {
-                    true => "class=\"fill-black\"",
-                    false => "fill=\"#d55f6f\"",
+        let rank = rank.clamp(1, 10);
+        let (viewbox, layout, face_x) = match self.style {
+            Style::Face => ("6 6.1 98 97".to_string(), Vec::new(), 0.0),
+            Style::Badge => badge_layout(name, uv, rv, rank),
+            Style::Flat => flat_layout(name, uv, rv),
+            Style::Pill => pill_layout(rank),
+            Style::Card => card_layout(uv, rv),
+            Style::Spark => spark_layout(&self.series),
+            Style::Rank => rank_layout(&self.standing),
+        };
+        // 卡片的脸在上方居中，其余版式的脸在左侧
+        let face_y = match self.style {
+            Style::Card => 8.0,
+            _ => 0.0,
+        };
+
+        Element::new("svg")
+            .attr("viewBox", viewbox)
+            .attr("xmlns", "http://www.w3.org/2000/svg")
+            .child(Element::new("defs").child(Element::new("style").text(self.css())))
+            .children(layout)
+            .child(face(rank, face_x, face_y))
+            .to_document()
+    }
+
+    /// 实际使用的 (fill_white, fill_black)，auto 模式取日间配色
+    pub fn colors(&self) -> (&str, &str) {
+        // dark 模式直接使用 auto 模式下暗色的配色
+        match self.mode {
+            Mode::Dark => (&self.fill_black, &self.fill_white),
+            _ => (&self.fill_white, &self.fill_black),
+        }
+    }
+
+    fn css(&self) -> String {
+        let (fill_white, fill_black) = self.colors();
+        SVG_STYLE
+            .replace(
+                "#dark_media#",
+                match self.mode {
+                    Mode::Auto => SVG_DARK_MEDIA,
+                    _ => "",
                 },
-                35.5 + (i as f64) * 4f64
-            ));
+            )
+            .replace("#stroke#", &border(fill_white))
+            .replace("#stroke_dark#", &border(fill_black))
+            .replace("#fill_white#", fill_white)
+            .replace("#fill_black#", fill_black)
+            .replace("#fill_empty#", &self.fill_empty)
+    }
+}
+
+// 无聊脸，占据 (6, 6) 到 (104, 104) 的区域，下方牙齿按等级点亮
+fn face(rank: i64, x: f64, y: f64) -> Element {
+    let teeth = (0..10).map(|i| {
+        Element::new("rect")
+            .attr(
+                "class",
+                match i < rank {
+                    true => "fill-black",
+                    false => "fill-empty",
+                },
+            )
+            .attr("x", 35.5 + (i as f64) * 4f64)
+            .attr("y", 72.5)
+            .attr("width", 3)
+            .attr("height", 6)
+            .attr("rx", 1)
+    });
+    Element::new("g")
+        .attr("transform", format!("translate({} {})", px(x), px(y)))
+        .child(
+            Element::new("g")
+                .attr("transform", "matrix(1, 0, 0, 1, 1.062999, 0.155999)")
+                .attr("fill", "none")
+                .attr("fill-rule", "evenodd")
+                .child(
+                    Element::new("circle")
+                        .attr("class", "fill-black")
+                        .attr("cx", 54.5)
+                        .attr("cy", 54.5)
+                        .attr("r", 48.5),
+                )
+                .child(
+                    Element::new("g")
+                        .attr("class", "fill-white")
+                        .attr("transform", "translate(19.587 30.779)")
+                        .child(eye(14.861))
+                        .child(eye(54.966)),
+                )
+                .child(
+                    Element::new("rect")
+                        .attr("class", "fill-white")
+                        .attr("x", 31.5)
+                        .attr("y", 71.2)
+                        .attr("width", 47)
+                        .attr("height", 8.529)
+                        .attr("rx", 5),
+                )
+                .children(teeth),
+        )
+}
+
+fn eye(cx: f64) -> Element {
+    Element::new("circle")
+        .attr("cx", cx)
+        .attr("cy", 13.861)
+        .attr("r", 8.861)
+}
+
+fn rect(class: &str, x: f64, width: f64, height: f64, rx: f64) -> Element {
+    Element::new("rect")
+        .attr("class", class)
+        .attr("x", px(x))
+        .attr("width", px(width))
+        .attr("height", px(height))
+        .attr("rx", px(rx))
+}
+
+// text-anchor 为 start 或 middle
+fn text(content: String, size: f64, x: f64, y: f64, anchor: &str) -> Element {
+    Element::new("text")
+        .attr(
+            "style",
+            format!(
+                "font-size: {}px; white-space: pre; text-anchor: {};",
+                px(size),
+                anchor
+            ),
+        )
+        .attr("x", px(x))
+        .attr("y", y)
+        .text(content)
+}
+
+// 带边框的宽徽章。文字放不下时先缩小字号，缩到下限仍放不下再加宽徽章
+fn badge_layout(name: &str, uv: i64, rv: i64, rank: i64) -> (String, Vec<Element>, f64) {
+    let name = format!("{} 🥱", name);
+    let stats = format!("UV: {}  Referer: {}  Level: {}", uv, rv, rank);
+    let (name_size, name_width) = fit(&name, 38.0, 26.0, BADGE_TEXT_AREA - 40.0);
+    let (stats_size, stats_width) = fit(&stats, 24.0, 18.0, BADGE_TEXT_AREA - 40.0);
+    let area = BADGE_TEXT_AREA.max(name_width.max(stats_width) + 40.0);
+    let total_width = BADGE_TEXT_LEFT + area + BADGE_RIGHT_PADDING;
+    let text_x = BADGE_TEXT_LEFT + area / 2.0;
+    (
+        format!("-3.412 -2.5 {} 115.604", px(total_width + 6.5)),
+        vec![
+            rect("fill-white border", 0.0, total_width, 110.0, 50.0),
+            text(name, name_size, text_x, 48.074, "middle"),
+            text(stats, stats_size, text_x, 91.615, "middle").attr("font-style", "italic"),
+        ],
+        0.0,
+    )
+}
+
+// shields.io 风格的双段徽章，左段为脸与站名，右段为 UV 与 RV。站名过长时适当缩小
+fn flat_layout(name: &str, uv: i64, rv: i64) -> (String, Vec<Element>, f64) {
+    let value = format!("UV {} · RV {}", uv, rv);
+    let (name_size, name_width) = fit(name, 44.0, 32.0, 600.0);
+    let value_width = text_width(&value, 44.0) + 48.0;
+    let label_width = 112.0 + name_width + 24.0;
+    let total_width = label_width + value_width;
+    (
+        format!("0 0 {} 110", px(total_width)),
+        vec![
+            rect("fill-white", 0.0, total_width, 110.0, 14.0),
+            rect("fill-black", label_width, value_width, 110.0, 14.0),
+            // 盖住右段左侧的圆角
+            rect("fill-black", label_width, 28.0, 110.0, 0.0),
+            text(name.to_string(), name_size, 112.0, 70.0, "start"),
+            text(value, 44.0, label_width + value_width / 2.0, 70.0, "middle")
+                .attr("class", "fill-white"),
+        ],
+        0.0,
+    )
+}
+
+// 只有脸与等级的胶囊
+fn pill_layout(rank: i64) -> (String, Vec<Element>, f64) {
+    let level = format!("Lv.{}", rank);
+    let total_width = 114.0 + text_width(&level, 52.0) + 34.0;
+    (
+        format!("0 0 {} 110", px(total_width)),
+        vec![
+            rect("fill-white", 0.0, total_width, 110.0, 55.0),
+            text(level, 52.0, 114.0, 73.0, "start"),
+        ],
+        0.0,
+    )
+}
+
+// 方形卡片，脸在上，UV 与 RV 上下排列。数字过长时先缩小字号，仍放不下再加宽卡片
+fn card_layout(uv: i64, rv: i64) -> (String, Vec<Element>, f64) {
+    let (uv_text, rv_text) = (format!("UV: {}", uv), format!("Referer: {}", rv));
+    let longest = match text_width(&uv_text, 1.0) > text_width(&rv_text, 1.0) {
+        true => &uv_text,
+        false => &rv_text,
+    };
+    let (stats_size, stats_width) = fit(longest, 34.0, 24.0, CARD_WIDTH - 20.0);
+    let total_width = CARD_WIDTH.max(stats_width + 20.0);
+    let text_x = total_width / 2.0;
+    (
+        format!("-2.5 -2.5 {} 245", px(total_width + 5.0)),
+        vec![
+            rect("fill-white border", 0.0, total_width, 240.0, 28.0),
+            text(uv_text, stats_size, text_x, 168.0, "middle"),
+            text(rv_text, stats_size, text_x, 214.0, "middle"),
+        ],
+        total_width / 2.0 - 55.0,
+    )
+}
+
+// 脸右侧为逐日柱状图，再右侧为区间合计。没有访问的日子只画一条底线
+fn spark_layout(series: &[i64]) -> (String, Vec<Element>, f64) {
+    let max = series.iter().copied().max().unwrap_or(0).max(1);
+    let pitch = SPARK_WIDTH / series.len().max(1) as f64;
+    let bars = series.iter().enumerate().map(|(i, v)| {
+        let (class, height) = match *v {
+            0 => ("fill-empty", 3.0),
+            v => (
+                "fill-black",
+                (v as f64 / max as f64 * SPARK_HEIGHT).max(4.0),
+            ),
+        };
+        rect(class, 114.0 + i as f64 * pitch, pitch * 0.7, height, 0.0).attr("y", px(92.0 - height))
+    });
+    let total = series.iter().sum::<i64>().to_string();
+    let days = format!("{} days", series.len());
+    let label_width = text_width(&total, 44.0).max(text_width(&days, 24.0));
+    let label_x = 114.0 + SPARK_WIDTH + 16.0;
+    let total_width = label_x + label_width + 24.0;
+    let mut layout = vec![rect("fill-white", 0.0, total_width, 110.0, 14.0)];
+    layout.extend(bars);
+    layout.push(text(total, 44.0, label_x, 62.0, "start"));
+    layout.push(text(days, 24.0, label_x, 92.0, "start"));
+    (format!("0 0 {} 110", px(total_width)), layout, 0.0)
+}
+
+// 脸右侧上行为月榜名次，下行为总排行名次，名次后标出较昨日的升降
+fn rank_layout(standing: &Standing) -> (String, Vec<Element>, f64) {
+    let line = |position: Option<i64>, prev: Option<i64>, label: &str| match position {
+        Some(n) => format!("#{} {}{}", n, label, movement(n, prev)),
+        None => format!("unranked {}", label),
+    };
+    let monthly = line(standing.monthly, standing.monthly_prev, "this month");
+    let overall = line(standing.overall, standing.overall_prev, "all time");
+    let (monthly_size, monthly_width) = fit(&monthly, 40.0, 30.0, 420.0);
+    let (overall_size, overall_width) = fit(&overall, 26.0, 20.0, 420.0);
+    let total_width = 114.0 + monthly_width.max(overall_width) + 24.0;
+    (
+        format!("0 0 {} 110", px(total_width)),
+        vec![
+            rect("fill-white", 0.0, total_width, 110.0, 14.0),
+            text(monthly, monthly_size, 114.0, 58.0, "start"),
+            text(overall, overall_size, 114.0, 92.0, "start"),
+        ],
+        0.0,
+    )
+}
+
+// 名次数字越小越靠前，上升用 ▲，下降用 ▼，昨日未上榜记为 new
+fn movement(position: i64, prev: Option<i64>) -> String {
+    match prev {
+        Some(p) if position < p => format!(" ▲{}", p - position),
+        Some(p) if position > p => format!(" ▼{}", position - p),
+        Some(_) => String::new(),
+        None => " new".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHORT: &str = "无聊湾";
+    const LONG: &str = "一个名字特别特别长的中文博客站点 🥱🚀✨ The Boring Bay";
+
+    fn badge(style: Style) -> BoringFace {
+        BoringFace::new("#d0273e".to_string(), "#f5acb9".to_string(), true).themed(
+            None,
+            Mode::Auto,
+            Some(style),
+        )
+    }
+
+    #[test]
+    fn face_only() {
+        let face = BoringFace::new("#f5acb9".to_string(), "#d0273e".to_string(), false);
+        insta::assert_snapshot!(face.render_svg(SHORT, 12, 3, 4));
+    }
+
+    #[test]
+    fn badge_layouts() {
+        for style in [Style::Badge, Style::Flat, Style::Pill, Style::Card] {
+            let face = badge(style);
+            let name = format!("{:?}", style).to_lowercase();
+            insta::assert_snapshot!(format!("{}_short", name), face.render_svg(SHORT, 12, 3, 4));
+            insta::assert_snapshot!(
+                format!("{}_long_name", name),
+                face.render_svg(LONG, 12, 3, 4)
+            );
+            insta::assert_snapshot!(
+                format!("{}_large_numbers", name),
+                face.render_svg(SHORT, 987_654_321, 123_456_789, 10)
+            );
         }
+    }
 
-        content.push_str(SVG_FOOTER);
+    #[test]
+    fn themed_dark() {
+        let face = badge(Style::Badge).themed(Palette::named("ocean"), Mode::Dark, None);
+        insta::assert_snapshot!(face.render_svg(SHORT, 12, 3, 4));
+    }
 
-        content = content.replace(
-            "#svg_viewport#",
-            match self.with_border {
-                true => "viewBox=\"-3.412 -2.5 584.261 115.604\"",
-                _ => "viewBox=\"6 6.1 98 97\"",
-            },
+    #[test]
+    fn spark_layout() {
+        let face = badge(Style::Spark);
+        insta::assert_snapshot!(
+            "spark_week",
+            face.clone()
+                .with_series(vec![3, 0, 12, 40, 7, 0, 25])
+                .render_svg(SHORT, 12, 3, 4)
+        );
+        insta::assert_snapshot!(
+            "spark_large_numbers",
+            face.with_series(vec![987_654_321; 30])
+                .render_svg(SHORT, 12, 3, 4)
         );
+    }
 
-        if self.with_border {
-            content = content.replace(
-                "#svg_border#",
-                &SVG_BORDER
-                    .replace("#site_name#", name)
-                    .replace("#site_uv#", &uv.to_string())
-                    .replace("#site_referrer#", &rv.to_string())
-                    .replace("#site_rank#", &rank.to_string()),
+    #[test]
+    fn rank_layout() {
+        let face = badge(Style::Rank);
+        insta::assert_snapshot!(
+            "rank_moved",
+            face.clone()
+                .with_standing(Standing {
+                    monthly: Some(3),
+                    monthly_prev: Some(5),
+                    overall: Some(12),
+                    overall_prev: Some(11),
+                })
+                .render_svg(SHORT, 12, 3, 4)
+        );
+        insta::assert_snapshot!(
+            "rank_unranked",
+            face.with_standing(Standing {
+                monthly: None,
+                monthly_prev: None,
+                overall: Some(1234),
+                overall_prev: None,
+            })
+            .render_svg(SHORT, 12, 3, 4)
+        );
+    }
+
+    #[test]
+    fn hostile_name_is_escaped() {
+        use resvg::usvg::roxmltree;
+
+        let name = "<script>alert(1)</script>&\"'\u{7}";
+        // 只有这两种版式显示站点名
+        for style in [Style::Badge, Style::Flat] {
+            let svg = badge(style).render_svg(name, 12, 3, 4);
+            assert!(!svg.contains("<script"), "{:?}", style);
+            assert!(!svg.contains('\u{7}'), "{:?}", style);
+            let doc = roxmltree::Document::parse(&svg)
+                .unwrap_or_else(|e| panic!("{:?} is not well-formed: {}", style, e));
+            assert!(doc.descendants().all(|n| n.tag_name().name() != "script"));
+            // 控制字符被去掉，其余原样显示为文字
+            assert!(
+                doc.descendants()
+                    .filter_map(|n| n.text())
+                    .any(|t| t.contains("<script>alert(1)</script>&\"'")),
+                "{:?}",
+                style
             );
         }
-
-        content
     }
 }
@@ -0,0 +1,202 @@
+use headers::HeaderMap;
+use lazy_static::lazy_static;
+use regex::{RegexSet, RegexSetBuilder};
+
+lazy_static! {
+    // 特征列表维护在 resources/bot_patterns.txt，编译期嵌入
+    static ref BOT_PATTERNS: RegexSet = RegexSetBuilder::new(
+        include_str!("../resources/bot_patterns.txt")
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#')),
+    )
+    .case_insensitive(true)
+    .build()
+    .unwrap();
+}
+
+/// 判定为机器人的原因
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BotReason {
+    /// User-Agent 命中特征列表
+    UserAgent,
+    /// 没有 User-Agent
+    MissingUserAgent,
+    /// 浏览器请求图片和页面都会带 Accept
+    MissingAccept,
+    /// 浏览器预取、预渲染，访客未必真正打开
+    Prefetch,
+}
+
+/// 按请求头判断是否为爬虫、链接预览、监控等非真人访问
+pub fn classify(headers: &HeaderMap) -> Option<BotReason> {
+    let user_agent = header_str(headers, "User-Agent").unwrap_or_default();
+    if user_agent.trim().is_empty() {
+        return Some(BotReason::MissingUserAgent);
+    }
+    if BOT_PATTERNS.is_match(user_agent) {
+        return Some(BotReason::UserAgent);
+    }
+    if header_str(headers, "Accept").is_none() {
+        return Some(BotReason::MissingAccept);
+    }
+    let prefetch = ["Purpose", "Sec-Purpose", "X-Purpose", "X-Moz"]
+        .iter()
+        .filter_map(|name| header_str(headers, name))
+        .any(|v| {
+            let v = v.to_ascii_lowercase();
+            v.contains("prefetch") || v.contains("preview") || v.contains("prerender")
+        });
+    if prefetch {
+        return Some(BotReason::Prefetch);
+    }
+    None
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headers::HeaderValue;
+
+    // 浏览器加载图片时的 Accept
+    const IMAGE_ACCEPT: &str = "image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8";
+    const CHROME: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+    fn request(user_agent: &str, extra: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("User-Agent", HeaderValue::from_str(user_agent).unwrap());
+        headers.insert("Accept", HeaderValue::from_static(IMAGE_ACCEPT));
+        for (name, value) in extra {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn bots_are_detected_by_user_agent() {
+        let bots = [
+            // 搜索引擎
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+            "Mozilla/5.0 (Linux; Android 6.0.1; Nexus 5X Build/MMB29P) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/119.0.6045.199 Mobile Safari/537.36 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+            "Googlebot-Image/1.0",
+            "Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)",
+            "Mozilla/5.0 AppleWebKit/537.36 (KHTML, like Gecko; compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm) Chrome/116.0.1938.76 Safari/537.36",
+            "Mozilla/5.0 (compatible; Baiduspider/2.0; +http://www.baidu.com/search/spider.html)",
+            "Mozilla/5.0 (compatible; YandexBot/3.0; +http://yandex.com/bots)",
+            "Sogou web spider/4.0(+http://www.sogou.com/docs/help/webmasters.htm#07)",
+            "Sogou Pic Spider/3.0(+http://www.sogou.com/docs/help/webmasters.htm#07)",
+            "Mozilla/5.0 (Linux;u;Android 4.2.2;zh-cn;) AppleWebKit/534.46 (KHTML,like Gecko) Version/5.1 Mobile Safari/10600.6.3 (compatible; Bytespider; spider-feedback@bytedance.com)",
+            // 链接预览
+            "facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)",
+            "Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)",
+            "Slackbot 1.0 (+https://api.slack.com/robots)",
+            "Twitterbot/1.0",
+            "Mozilla/5.0 (compatible; Discordbot/2.0; +https://discordapp.com)",
+            "TelegramBot (like TwitterBot)",
+            "WhatsApp/2.23.20.0",
+            "Mastodon/4.2.0 (http.rb/5.1.1; +https://mastodon.social/)",
+            // 监控
+            "Mozilla/5.0+(compatible; UptimeRobot/2.0; http://www.uptimerobot.com/)",
+            "Pingdom.com_bot_version_1.4_(http://www.pingdom.com/)",
+            "Uptime-Kuma/1.23.0",
+            // 命令行与库
+            "curl/8.4.0",
+            "Wget/1.21.4",
+            "python-requests/2.31.0",
+            "Go-http-client/1.1",
+            "okhttp/4.12.0",
+            "Java/17.0.2",
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) HeadlessChrome/120.0.6099.109 Safari/537.36",
+        ];
+        for ua in bots {
+            assert_eq!(
+                classify(&request(ua, &[])),
+                Some(BotReason::UserAgent),
+                "{}",
+                ua
+            );
+        }
+    }
+
+    #[test]
+    fn browsers_are_not_bots() {
+        let browsers = [
+            // Chrome
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+            "Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+            // Safari iOS
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_1_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.1 Mobile/15E148 Safari/604.1",
+            "Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1",
+            // Firefox
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:121.0) Gecko/20100101 Firefox/121.0",
+            "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0",
+            // Edge、Samsung Internet
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36 Edg/120.0.0.0",
+            "Mozilla/5.0 (Linux; Android 13; SAMSUNG SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) SamsungBrowser/23.0 Chrome/115.0.0.0 Mobile Safari/537.36",
+            // 微信内置浏览器
+            "Mozilla/5.0 (Linux; Android 12; PGBM10 Build/SP1A.210812.016; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/107.0.5304.141 Mobile Safari/537.36 XWEB/5023 MMWEBSDK/20221206 MMWEBID/2585 MicroMessenger/8.0.32.2300(0x2800205D) WeChat/arm64 Weixin NetType/WIFI Language/zh_CN ABI/arm64",
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 16_3 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 MicroMessenger/8.0.33(0x18002129) NetType/WIFI Language/zh_CN",
+            // QQ 内置浏览器
+            "Mozilla/5.0 (Linux; Android 10; V1981A Build/QP1A.190711.020; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/83.0.4103.106 Mobile Safari/537.36 V1_AND_SQ_8.8.68_2538_YYB_D A_8086800 QQ/8.8.68.7265 NetType/WIFI WebP/0.3.0 Pixel/1080 StatusBarHeight/76 SimpleUISwitch/0 QQTheme/1000 InMagicWin/0 StudyMode/0 CurrentMode/0 CurrentFontScale/1.0",
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 15_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 QQ/8.8.88.602 V1_IPH_SQ_8.8.88_1_APP_A Pixel/1170 MiniAppEnable SimpleUISwitch/0 StudyMode/0 CurrentMode/0 CurrentFontScale/1.000000 QQTheme/1000 Core/WKWebView Device/Apple(iPhone 13) NetType/WIFI QBWebViewType/1 WKType/1",
+            // 搜狗浏览器
+            "Mozilla/5.0 (Windows NT 10.0; WOW64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/86.0.4240.198 Safari/537.36 SE 2.X MetaSr 1.0",
+            "Mozilla/5.0 (Linux; Android 10; HMA-AL00 Build/HUAWEIHMA-AL00; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/80.0.3987.99 Mobile Safari/537.36 SogouMSE,SogouMobileBrowser/5.39.11",
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 16_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 SogouMobileBrowser/6.0.2",
+            // QQ 浏览器
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/94.0.4606.71 Safari/537.36 Core/1.94.202.400 QQBrowser/11.9.5355.400",
+            "Mozilla/5.0 (Linux; U; Android 12; zh-cn; PFEM10 Build/SKQ1.211019.001) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/109.0.5414.86 MQQBrowser/14.0 Mobile Safari/537.36 COVC/046401",
+            // UC 浏览器
+            "Mozilla/5.0 (Linux; U; Android 10; zh-CN; V2001A Build/QP1A.190711.020) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/78.0.3904.108 UCBrowser/13.4.2.1122 Mobile Safari/537.36",
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 16_6 like Mac OS X; zh-CN) AppleWebKit/537.51.1 (KHTML, like Gecko) Mobile/15E148 UCBrowser/15.5.6.2102 Mobile AliApp(TUnion/2.0)",
+            // 型号里带 bot 的手机
+            "Mozilla/5.0 (Linux; Android 10; CUBOT X30) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+            "Mozilla/5.0 (Linux; Android 11; KINGKONG 7 Build/RP1A.200720.011; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/120.0.0.0 Mobile Safari/537.36",
+        ];
+        for ua in browsers {
+            assert_eq!(classify(&request(ua, &[])), None, "{}", ua);
+        }
+    }
+
+    #[test]
+    fn missing_headers_are_bots() {
+        assert_eq!(
+            classify(&HeaderMap::new()),
+            Some(BotReason::MissingUserAgent)
+        );
+        assert_eq!(
+            classify(&request("  ", &[])),
+            Some(BotReason::MissingUserAgent)
+        );
+        let mut headers = request(CHROME, &[]);
+        headers.remove("Accept");
+        assert_eq!(classify(&headers), Some(BotReason::MissingAccept));
+    }
+
+    #[test]
+    fn prefetch_is_not_a_visit() {
+        for header in [
+            ("Sec-Purpose", "prefetch"),
+            ("Sec-Purpose", "prefetch;prerender"),
+            ("Purpose", "prefetch"),
+            ("X-Moz", "prefetch"),
+            ("X-Purpose", "preview"),
+        ] {
+            assert_eq!(
+                classify(&request(CHROME, &[header])),
+                Some(BotReason::Prefetch),
+                "{:?}",
+                header
+            );
+        }
+        assert_eq!(
+            classify(&request(CHROME, &[("Sec-Fetch-Dest", "image")])),
+            None
+        );
+    }
+}
@@ -0,0 +1,674 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt, fs,
+    net::SocketAddr,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context as _};
+use chrono_tz::Tz;
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer};
+
+/// 环境变量覆盖前缀，`BORINGBAY_<SECTION>__<KEY>` 覆盖对应配置项，
+/// 例如 `BORINGBAY_SERVER__BIND=127.0.0.1:8080`
+const ENV_PREFIX: &str = "BORINGBAY_";
+
+// 打印配置时代替密码与密钥
+const REDACTED: &str = "<redacted>";
+
+/// 兼容旧部署的环境变量
+const LEGACY_ENV: [(&str, &str); 3] = [
+    ("DATABASE_URL", "database.url"),
+    ("SYSTEM_DOMAIN", "server.system_domain"),
+    ("TIMEZONE", "server.timezone"),
+];
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub counter: CounterConfig,
+    pub rank: RankConfig,
+    pub badge: BadgeConfig,
+    pub privacy: PrivacyConfig,
+    pub anomaly: AnomalyConfig,
+    pub admin: AdminConfig,
+    pub rate_limit: RateLimitConfig,
+    pub embed: EmbedConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// 监听地址，默认 `0.0.0.0:3000`
+    pub bind: SocketAddr,
+    /// 系统域名，来自该域名的 referrer 不计数，必填
+    pub system_domain: String,
+    /// 日切与页面展示所用时区，默认 `Asia/Shanghai`
+    #[serde(deserialize_with = "deserialize_tz")]
+    pub timezone: Tz,
+    /// 会员列表文件，默认 `./resources/membership.json`
+    pub membership_path: String,
+    /// IP 段与国家访问名单，默认 `./access.toml`，不存在时全部放行
+    pub access_path: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            system_domain: "".to_string(),
+            timezone: chrono_tz::Asia::Shanghai,
+            membership_path: "./resources/membership.json".to_string(),
+            access_path: "./access.toml".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// SQLite 数据库路径，必填
+    pub url: String,
+    /// 连接池大小，默认 5
+    pub pool_size: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "".to_string(),
+            pool_size: 5,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CounterConfig {
+    /// 同一访客重复访问不计数的时长（秒），默认 4 小时
+    pub dedup_ttl_secs: u64,
+    /// 访客去重缓存清理过期项的间隔（秒），默认 10 分钟
+    pub cache_sweep_secs: u64,
+    /// 计数写入数据库并刷新排行的间隔（秒），默认 5 分钟
+    pub flush_interval_secs: u64,
+    /// 等级 = 今日访问量 / (上日活跃站点平均访问量 / level_divisor)，默认 10
+    pub level_divisor: i64,
+}
+
+impl Default for CounterConfig {
+    fn default() -> Self {
+        Self {
+            dedup_ttl_secs: 60 * 60 * 4,
+            cache_sweep_secs: 60 * 10,
+            flush_interval_secs: 60 * 5,
+            level_divisor: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RankConfig {
+    /// 超过该天数无访问的站点列入即将移除，同时也是月榜窗口，默认 30 天
+    pub stale_days: i64,
+    /// 首页月榜展示数量，默认 10
+    pub homepage_limit: usize,
+}
+
+impl Default for RankConfig {
+    fn default() -> Self {
+        Self {
+            stale_days: 30,
+            homepage_limit: 10,
+        }
+    }
+}
+
+/// 对发送 `DNT: 1` 或 `Sec-GPC: 1` 的访客的处理，这类访客仍会匿名计数
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrivacyConfig {
+    /// 不在首页实时动态中广播，默认开启
+    pub skip_broadcast: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            skip_broadcast: true,
+        }
+    }
+}
+
+/// 流量异常检测，每天日切后检测上一天的数据
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnomalyConfig {
+    /// 是否启用，默认开启
+    pub enabled: bool,
+    /// 与自身历史比较的天数，默认 14
+    pub history_days: i64,
+    /// 当日 UV + RV 低于该值不检测，默认 50
+    pub min_views: i64,
+    /// 超过自身历史均值多少倍标准差视为异常，默认 4
+    pub history_sigma: f64,
+    /// 超过全湾中位数多少倍（稳健 z 分数）视为异常，默认 6
+    pub bay_score: f64,
+    /// 访客网段数 / 访客数低于该值视为异常，默认 0.1
+    pub min_network_ratio: f64,
+    /// 访客国家分布的香农熵（比特）高于该值视为异常，默认 4
+    pub max_country_entropy: f64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            history_days: 14,
+            min_views: 50,
+            history_sigma: 4.0,
+            bay_score: 6.0,
+            min_network_ratio: 0.1,
+            max_country_entropy: 4.0,
+        }
+    }
+}
+
+/// 按客户端 IP 的限流，各路由分组预算独立，超限返回 429
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// 是否启用，默认开启
+    pub enabled: bool,
+    pub badge: Budget,
+    pub icon: Budget,
+    pub favicon: Budget,
+    pub ws: Budget,
+    /// 首页、排行等页面与 embed.js
+    pub page: Budget,
+    /// 管理后台，预算较紧以防暴力破解密码
+    pub admin: Budget,
+    /// 单个 IP 的 WebSocket 并发连接数上限，默认 10
+    pub ws_max_per_ip: usize,
+    /// WebSocket 并发连接总数上限，默认 2000
+    pub ws_max_total: usize,
+    /// 可信代理的网段，只有连接来自这些网段时才采信 `CF-Connecting-IP` 与 `CF-IPCountry`，默认为 Cloudflare 的网段
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            badge: Budget::new(60, 2.0),
+            icon: Budget::new(60, 2.0),
+            favicon: Budget::new(60, 2.0),
+            ws: Budget::new(10, 0.2),
+            page: Budget::new(30, 1.0),
+            admin: Budget::new(10, 0.1),
+            ws_max_per_ip: 10,
+            ws_max_total: 2000,
+            trusted_proxies: CLOUDFLARE_CIDRS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+// https://www.cloudflare.com/ips/
+const CLOUDFLARE_CIDRS: [&str; 22] = [
+    "173.245.48.0/20",
+    "103.21.244.0/22",
+    "103.22.200.0/22",
+    "103.31.4.0/22",
+    "141.101.64.0/18",
+    "108.162.192.0/18",
+    "190.93.240.0/20",
+    "188.114.96.0/20",
+    "197.234.240.0/22",
+    "198.41.128.0/17",
+    "162.158.0.0/15",
+    "104.16.0.0/13",
+    "104.24.0.0/14",
+    "172.64.0.0/13",
+    "131.0.72.0/22",
+    "2400:cb00::/32",
+    "2606:4700::/32",
+    "2803:f800::/32",
+    "2405:b500::/32",
+    "2405:8100::/32",
+    "2a06:98c0::/29",
+    "2c0f:f248::/32",
+];
+
+/// 令牌桶预算：最多积攒 `burst` 个令牌，每秒补充 `per_second` 个
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Budget {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl Budget {
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// 徽章嵌入令牌，防止他人伪造 Referer 把访问记到某个站点名下。
+/// 令牌写在成员页面源码里，拿到它的人（包括成员自己）仍可伪造请求，
+/// 自刷流量交给访客去重、机器人识别、限流与异常检测处理
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbedConfig {
+    /// `naive embed-token` 签发令牌的有效天数，默认 365
+    pub token_ttl_days: i64,
+    /// 站点域名到密钥的映射。设置了密钥的站点，未携带有效令牌的徽章访问只计入未验证
+    pub secrets: HashMap<String, String>,
+}
+
+impl Default for EmbedConfig {
+    fn default() -> Self {
+        Self {
+            token_ttl_days: 365,
+            secrets: HashMap::new(),
+        }
+    }
+}
+
+// check-config 会打印配置，密钥只显示是否设置
+impl fmt::Debug for EmbedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut domains = self.secrets.keys().collect::<Vec<_>>();
+        domains.sort();
+        f.debug_struct("EmbedConfig")
+            .field("token_ttl_days", &self.token_ttl_days)
+            .field(
+                "secrets",
+                &domains
+                    .into_iter()
+                    .map(|d| (d, REDACTED))
+                    .collect::<BTreeMap<_, _>>(),
+            )
+            .finish()
+    }
+}
+
+/// 管理后台，使用 HTTP Basic 认证，用户名固定为 admin
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// 管理密码，为空时关闭管理后台
+    pub password: String,
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field(
+                "password",
+                &match self.password.is_empty() {
+                    true => "",
+                    false => REDACTED,
+                },
+            )
+            .finish()
+    }
+}
+
+/// 图标配色，默认取人民币的红，favicon 为反色
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BadgeConfig {
+    pub badge: FaceColors,
+    pub favicon: FaceColors,
+    pub icon: FaceColors,
+    /// 不计数的徽章 `/api/render/:domain` 的缓存时长（秒），默认 60
+    pub render_max_age_secs: u64,
+    /// 补充字体的目录，默认 `./resources/fonts`，PNG 徽章中嵌入字体缺少的字形先在这里查找，再用系统字体补足
+    pub font_dir: String,
+    /// 栅格化结果的缓存时长（秒），默认 600
+    pub png_cache_secs: u64,
+}
+
+impl Default for BadgeConfig {
+    fn default() -> Self {
+        Self {
+            badge: FaceColors::new("#d0273e", "#f5acb9"),
+            favicon: FaceColors::new("#f5acb9", "#d0273e"),
+            icon: FaceColors::new("#d0273e", "#f5acb9"),
+            render_max_age_secs: 60,
+            font_dir: "./resources/fonts".to_string(),
+            png_cache_secs: 600,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FaceColors {
+    pub fill_white: String,
+    pub fill_black: String,
+}
+
+impl FaceColors {
+    fn new(fill_white: &str, fill_black: &str) -> Self {
+        Self {
+            fill_white: fill_white.to_string(),
+            fill_black: fill_black.to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// 读取配置文件（不存在时使用默认值）并叠加环境变量覆盖，
+    /// 不做校验，由各子命令按用到的部分调用 validate*
+    pub fn load(path: &Path) -> Result<Config, anyhow::Error> {
+        let value = if path.exists() {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("failed to read config {}", path.display()))?;
+            content
+                .parse::<toml::Value>()
+                .with_context(|| format!("failed to parse config {}", path.display()))?
+        } else {
+            toml::Value::Table(toml::value::Table::new())
+        };
+        Self::with_env(value, &env::vars().collect(), path)
+    }
+
+    // 在配置文件内容上叠加环境变量
+    fn with_env(
+        mut value: toml::Value,
+        vars: &HashMap<String, String>,
+        path: &Path,
+    ) -> Result<Config, anyhow::Error> {
+        for (name, key) in LEGACY_ENV {
+            if let Some(v) = vars.get(name) {
+                let key = key.split('.').collect::<Vec<_>>();
+                set_value(&mut value, &key, toml::Value::String(v.clone()));
+            }
+        }
+        for (name, v) in vars {
+            if let Some(key) = name.strip_prefix(ENV_PREFIX) {
+                let key = key.to_lowercase();
+                let key = key.split("__").collect::<Vec<_>>();
+                if key.len() < 2 || key.iter().any(|k| k.is_empty()) {
+                    return Err(anyhow!("invalid config override {}", name));
+                }
+                let v = resolve_env_value(&value, &key, v);
+                set_value(&mut value, &key, v);
+            }
+        }
+
+        let config: Config = value
+            .try_into()
+            .with_context(|| format!("invalid config {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// 只用到数据库的子命令（migrate、export、import）
+    pub fn validate_database(&self) -> Result<(), anyhow::Error> {
+        if self.database.url.is_empty() {
+            return Err(anyhow!("database.url is required"));
+        }
+        if self.database.pool_size == 0 {
+            return Err(anyhow!("database.pool_size must be greater than 0"));
+        }
+        Ok(())
+    }
+
+    /// 签发嵌入令牌用到的站点域名与密钥
+    pub fn validate_embed(&self) -> Result<(), anyhow::Error> {
+        if self.server.system_domain.is_empty() {
+            return Err(anyhow!("server.system_domain is required"));
+        }
+        if self.embed.token_ttl_days <= 0 {
+            return Err(anyhow!("embed.token_ttl_days must be greater than 0"));
+        }
+        if let Some((domain, _)) = self.embed.secrets.iter().find(|(_, s)| s.len() < 16) {
+            return Err(anyhow!(
+                "embed.secrets of {} must be at least 16 characters",
+                domain
+            ));
+        }
+        Ok(())
+    }
+
+    /// 服务运行所需的全部配置
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        self.validate_database()?;
+        self.validate_embed()?;
+        if self.counter.dedup_ttl_secs == 0
+            || self.counter.cache_sweep_secs == 0
+            || self.counter.flush_interval_secs == 0
+        {
+            return Err(anyhow!("counter intervals must be greater than 0"));
+        }
+        if self.counter.level_divisor <= 0 {
+            return Err(anyhow!("counter.level_divisor must be greater than 0"));
+        }
+        if self.rank.stale_days <= 0 {
+            return Err(anyhow!("rank.stale_days must be greater than 0"));
+        }
+        if self.anomaly.history_days <= 0 {
+            return Err(anyhow!("anomaly.history_days must be greater than 0"));
+        }
+        if !(0.0..=1.0).contains(&self.anomaly.min_network_ratio) {
+            return Err(anyhow!("anomaly.min_network_ratio must be between 0 and 1"));
+        }
+        for (name, budget) in [
+            ("badge", &self.rate_limit.badge),
+            ("icon", &self.rate_limit.icon),
+            ("favicon", &self.rate_limit.favicon),
+            ("ws", &self.rate_limit.ws),
+            ("page", &self.rate_limit.page),
+            ("admin", &self.rate_limit.admin),
+        ] {
+            if budget.burst == 0 || budget.per_second <= 0.0 {
+                return Err(anyhow!(
+                    "rate_limit.{} burst and per_second must be greater than 0",
+                    name
+                ));
+            }
+        }
+        if self.rate_limit.ws_max_per_ip == 0 || self.rate_limit.ws_max_total == 0 {
+            return Err(anyhow!("rate_limit websocket caps must be greater than 0"));
+        }
+        if let Some(cidr) = self
+            .rate_limit
+            .trusted_proxies
+            .iter()
+            .find(|c| c.parse::<IpNet>().is_err())
+        {
+            return Err(anyhow!(
+                "rate_limit.trusted_proxies has invalid cidr {}",
+                cidr
+            ));
+        }
+        if self.badge.png_cache_secs == 0 {
+            return Err(anyhow!("badge.png_cache_secs must be greater than 0"));
+        }
+        for (name, colors) in [
+            ("badge", &self.badge.badge),
+            ("favicon", &self.badge.favicon),
+            ("icon", &self.badge.icon),
+        ] {
+            for color in [&colors.fill_white, &colors.fill_black] {
+                if !is_hex_color(color) {
+                    return Err(anyhow!("badge.{} has invalid color {}", name, color));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_value(root: &mut toml::Value, key: &[&str], v: toml::Value) {
+    let (last, parents) = key.split_last().unwrap();
+    let mut table = root;
+    for k in parents {
+        if let toml::Value::Table(t) = table {
+            table = t
+                .entry(k.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+        }
+    }
+    if let toml::Value::Table(t) = table {
+        t.insert(last.to_string(), v);
+    }
+}
+
+// 环境变量没有类型，先按 TOML 字面量解析；
+// 目标字段是字符串时（如纯数字的密码、形似日期的域名）字面量无法反序列化，退回字符串
+fn resolve_env_value(base: &toml::Value, key: &[&str], v: &str) -> toml::Value {
+    let typed = parse_env_value(v);
+    if typed.is_str() {
+        return typed;
+    }
+    let fits = |candidate: &toml::Value| {
+        let mut value = base.clone();
+        set_value(&mut value, key, candidate.clone());
+        value.try_into::<Config>().is_ok()
+    };
+    let string = toml::Value::String(v.to_string());
+    if !fits(&typed) && fits(&string) {
+        string
+    } else {
+        typed
+    }
+}
+
+// 按 TOML 字面量解析，失败时视为字符串
+fn parse_env_value(v: &str) -> toml::Value {
+    format!("v = {}", v)
+        .parse::<toml::Value>()
+        .ok()
+        .and_then(|t| t.get("v").cloned())
+        .unwrap_or_else(|| toml::Value::String(v.to_string()))
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn deserialize_tz<'de, D>(deserializer: D) -> Result<Tz, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse::<Tz>().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_with(file: &str, vars: &[(&str, &str)]) -> Result<Config, anyhow::Error> {
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::with_env(file.parse().unwrap(), &vars, Path::new("config.toml"))
+    }
+
+    #[test]
+    fn env_overrides_are_typed() {
+        let config = load_with(
+            "",
+            &[
+                ("BORINGBAY_SERVER__BIND", "127.0.0.1:8080"),
+                ("BORINGBAY_DATABASE__POOL_SIZE", "4"),
+                ("BORINGBAY_COUNTER__FLUSH_INTERVAL_SECS", "30"),
+                ("BORINGBAY_RANK__STALE_DAYS", "-1"),
+                ("BORINGBAY_RATE_LIMIT__ENABLED", "false"),
+                ("BORINGBAY_PRIVACY__SKIP_BROADCAST", "true"),
+                ("BORINGBAY_ANOMALY__HISTORY_SIGMA", "2.5"),
+                (
+                    "BORINGBAY_RATE_LIMIT__TRUSTED_PROXIES",
+                    r#"["10.0.0.0/8", "127.0.0.1/32"]"#,
+                ),
+                ("BORINGBAY_RATE_LIMIT__PAGE__BURST", "5"),
+                ("BORINGBAY_RATE_LIMIT__PAGE__PER_SECOND", "2"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.server.bind, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(config.database.pool_size, 4);
+        assert_eq!(config.counter.flush_interval_secs, 30);
+        assert_eq!(config.rank.stale_days, -1);
+        assert!(!config.rate_limit.enabled);
+        assert!(config.privacy.skip_broadcast);
+        assert_eq!(config.anomaly.history_sigma, 2.5);
+        assert_eq!(
+            config.rate_limit.trusted_proxies,
+            vec!["10.0.0.0/8".to_string(), "127.0.0.1/32".to_string()]
+        );
+        // 整数字面量用于浮点字段
+        assert_eq!(config.rate_limit.page.burst, 5);
+        assert_eq!(config.rate_limit.page.per_second, 2.0);
+    }
+
+    #[test]
+    fn string_fields_keep_literal_values() {
+        let config = load_with(
+            "",
+            &[
+                ("BORINGBAY_ADMIN__PASSWORD", "1234567890123456"),
+                ("BORINGBAY_SERVER__SYSTEM_DOMAIN", "2026-10-19"),
+                ("BORINGBAY_SERVER__MEMBERSHIP_PATH", "true"),
+                ("BORINGBAY_BADGE__FONT_DIR", "[fonts]"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.admin.password, "1234567890123456");
+        assert_eq!(config.server.system_domain, "2026-10-19");
+        assert_eq!(config.server.membership_path, "true");
+        assert_eq!(config.badge.font_dir, "[fonts]");
+    }
+
+    #[test]
+    fn env_overrides_file_and_legacy_names() {
+        let file = r#"
+            [server]
+            system_domain = "file.example.com"
+            [database]
+            url = "file.db"
+            pool_size = 2
+        "#;
+        let config = load_with(file, &[("DATABASE_URL", "legacy.db")]).unwrap();
+        assert_eq!(config.database.url, "legacy.db");
+        assert_eq!(config.database.pool_size, 2);
+        assert_eq!(config.server.system_domain, "file.example.com");
+
+        let config = load_with(
+            file,
+            &[
+                ("DATABASE_URL", "legacy.db"),
+                ("BORINGBAY_DATABASE__URL", "prefixed.db"),
+                ("SYSTEM_DOMAIN", "legacy.example.com"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.database.url, "prefixed.db");
+        assert_eq!(config.server.system_domain, "legacy.example.com");
+    }
+
+    #[test]
+    fn invalid_overrides_are_rejected() {
+        for vars in [
+            [("BORINGBAY_DATABASE__POOL_SIZE", "lots")],
+            [("BORINGBAY_RATE_LIMIT__ENABLED", "yes")],
+            [("BORINGBAY_RATE_LIMIT__TRUSTED_PROXIES", "10.0.0.0/8")],
+            [("BORINGBAY_SERVER", "x")],
+            [("BORINGBAY_SERVER__", "x")],
+            [("BORINGBAY_SERVER__UNKNOWN", "x")],
+            // 限流额度须同时给出 burst 与 per_second
+            [("BORINGBAY_RATE_LIMIT__PAGE__BURST", "5")],
+        ] {
+            assert!(load_with("", &vars).is_err(), "{:?}", vars);
+        }
+        // 其他前缀的变量不受影响
+        assert!(load_with("", &[("BORINGBAYX", "1"), ("HOME", "/root")]).is_ok());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use chrono::NaiveDateTime;
+
+use crate::statistics_model::Statistics;
+
+/// 单个站点的当日计数，热路径上只有原子操作
+#[derive(Default)]
+struct MemberCounter {
+    unique_visitor: AtomicI64,
+    updated_at: AtomicI64,
+    referrer: AtomicI64,
+    latest_referrer_at: AtomicI64,
+    bot: AtomicI64,
+    unverified: AtomicI64,
+}
+
+/// 某一时刻的计数快照，时间均为 UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemberCount {
+    pub unique_visitor: i64,
+    pub updated_at: NaiveDateTime,
+    pub referrer: i64,
+    pub latest_referrer_at: NaiveDateTime,
+    /// 被识别为机器人的访问，不计入 UV 与 RV
+    pub bot: i64,
+    /// 未携带有效嵌入令牌的徽章访问，不计入 UV
+    pub unverified: i64,
+}
+
+impl Default for MemberCount {
+    fn default() -> Self {
+        Self {
+            unique_visitor: 0,
+            updated_at: NaiveDateTime::from_timestamp(0, 0),
+            referrer: 0,
+            latest_referrer_at: NaiveDateTime::from_timestamp(0, 0),
+            bot: 0,
+            unverified: 0,
+        }
+    }
+}
+
+impl MemberCount {
+    pub fn to_statistics(self, membership_id: i64, created_at: NaiveDateTime) -> Statistics {
+        Statistics {
+            id: 0,
+            created_at,
+            membership_id,
+            unique_visitor: self.unique_visitor,
+            updated_at: self.updated_at,
+            referrer: self.referrer,
+            latest_referrer_at: self.latest_referrer_at,
+            bot: self.bot,
+            unverified: self.unverified,
+        }
+    }
+}
+
+/// 全部站点的当日计数。会员列表启动后不变，map 本身只读，
+/// 每个站点的计数器各自独立，并发访问不同站点互不影响
+pub struct Counters {
+    members: HashMap<i64, MemberCounter>,
+}
+
+impl Counters {
+    pub fn new<'a>(ids: impl Iterator<Item = &'a i64>) -> Self {
+        Self {
+            members: ids.map(|id| (*id, MemberCounter::default())).collect(),
+        }
+    }
+
+    /// 从数据库恢复当日计数
+    pub fn restore(&self, id: i64, count: MemberCount) {
+        if let Some(c) = self.members.get(&id) {
+            c.unique_visitor
+                .store(count.unique_visitor, Ordering::Relaxed);
+            c.updated_at
+                .store(to_nanos(&count.updated_at), Ordering::Relaxed);
+            c.referrer.store(count.referrer, Ordering::Relaxed);
+            c.latest_referrer_at
+                .store(to_nanos(&count.latest_referrer_at), Ordering::Relaxed);
+            c.bot.store(count.bot, Ordering::Relaxed);
+            c.unverified.store(count.unverified, Ordering::Relaxed);
+        }
+    }
+
+    pub fn incr_unique_visitor(&self, id: i64, at: NaiveDateTime) {
+        if let Some(c) = self.members.get(&id) {
+            c.unique_visitor.fetch_add(1, Ordering::Relaxed);
+            c.updated_at.fetch_max(to_nanos(&at), Ordering::Relaxed);
+        }
+    }
+
+    pub fn incr_referrer(&self, id: i64, at: NaiveDateTime) {
+        if let Some(c) = self.members.get(&id) {
+            c.referrer.fetch_add(1, Ordering::Relaxed);
+            c.latest_referrer_at
+                .fetch_max(to_nanos(&at), Ordering::Relaxed);
+        }
+    }
+
+    pub fn incr_bot(&self, id: i64) {
+        if let Some(c) = self.members.get(&id) {
+            c.bot.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn incr_unverified(&self, id: i64) {
+        if let Some(c) = self.members.get(&id) {
+            c.unverified.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, id: i64) -> MemberCount {
+        self.members.get(&id).map(load).unwrap_or_default()
+    }
+
+    pub fn snapshot(&self) -> HashMap<i64, MemberCount> {
+        self.members.iter().map(|(id, c)| (*id, load(c))).collect()
+    }
+
+    /// 取走全部计数并清零，用于跨天
+    pub fn reset(&self) -> HashMap<i64, MemberCount> {
+        self.members
+            .iter()
+            .map(|(id, c)| {
+                (
+                    *id,
+                    MemberCount {
+                        unique_visitor: c.unique_visitor.swap(0, Ordering::Relaxed),
+                        updated_at: from_nanos(c.updated_at.swap(0, Ordering::Relaxed)),
+                        referrer: c.referrer.swap(0, Ordering::Relaxed),
+                        latest_referrer_at: from_nanos(
+                            c.latest_referrer_at.swap(0, Ordering::Relaxed),
+                        ),
+                        bot: c.bot.swap(0, Ordering::Relaxed),
+                        unverified: c.unverified.swap(0, Ordering::Relaxed),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+fn load(c: &MemberCounter) -> MemberCount {
+    MemberCount {
+        unique_visitor: c.unique_visitor.load(Ordering::Relaxed),
+        updated_at: from_nanos(c.updated_at.load(Ordering::Relaxed)),
+        referrer: c.referrer.load(Ordering::Relaxed),
+        latest_referrer_at: from_nanos(c.latest_referrer_at.load(Ordering::Relaxed)),
+        bot: c.bot.load(Ordering::Relaxed),
+        unverified: c.unverified.load(Ordering::Relaxed),
+    }
+}
+
+fn to_nanos(t: &NaiveDateTime) -> i64 {
+    t.timestamp_nanos()
+}
+
+fn from_nanos(nanos: i64) -> NaiveDateTime {
+    NaiveDateTime::from_timestamp(
+        nanos.div_euclid(1_000_000_000),
+        nanos.rem_euclid(1_000_000_000) as u32,
+    )
+}
@@ -0,0 +1,58 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use r_cache::cache::Cache;
+use rand::RngCore;
+use sha2::Sha256;
+
+/// 访客去重缓存，只保存在内存中。
+/// 记录里只有访客标识的哈希，哈希盐每天随机生成且从不落盘，
+/// 因此重启后当天此前的访客会再计一次，这是为隐私付出的代价
+pub struct DedupCache {
+    cache: Cache<String, ()>,
+    ttl: Duration,
+    // 访客标识的哈希盐，每天随缓存一起轮换
+    salt: RwLock<[u8; 32]>,
+}
+
+impl DedupCache {
+    pub fn new(ttl: Duration, sweep: Duration) -> Self {
+        Self {
+            cache: Cache::new(Some(sweep)),
+            ttl,
+            salt: RwLock::new(random_salt()),
+        }
+    }
+
+    /// 以当日的盐对 IP 和 UA 做 HMAC，盐轮换后同一访客得到不同的标识，
+    /// 无法跨天关联；盐只在内存中，重启或跨天后即无法再由标识反推出 IP
+    pub fn visitor_hash(&self, ip: &str, user_agent: &str) -> String {
+        let salt = self.salt.read().unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(&*salt).expect("hmac accepts any key length");
+        mac.update(ip.as_bytes());
+        mac.update(b"\n");
+        mac.update(user_agent.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    pub async fn contains(&self, key: &str) -> bool {
+        self.cache.get(&key.to_string()).await.is_some()
+    }
+
+    pub async fn insert(&self, key: String) {
+        self.cache.set(key, (), Some(self.ttl)).await;
+    }
+
+    /// 跨天时清空，并换用新的随机哈希盐
+    pub async fn clear(&self) {
+        self.cache.clear().await;
+        *self.salt.write().unwrap() = random_salt();
+    }
+}
+
+fn random_salt() -> [u8; 32] {
+    let mut salt = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
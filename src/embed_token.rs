@@ -0,0 +1,137 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// 签名的徽章嵌入令牌，格式为 `<过期时间戳>.<签名>`，
+/// 签名为站点密钥对 `<域名>:<过期时间戳>` 的 HMAC-SHA256。
+/// 令牌在有效期内固定不变，只证明徽章地址由站点签发，不证明请求来自真实访客
+pub fn issue(secret: &str, domain: &str, expires: i64) -> String {
+    format!(
+        "{}.{}",
+        expires,
+        hex::encode(mac(secret, domain, expires).finalize().into_bytes())
+    )
+}
+
+/// 校验令牌，失败时返回原因
+pub fn verify(
+    secret: &str,
+    domain: &str,
+    token: Option<&str>,
+    now: i64,
+) -> Result<(), &'static str> {
+    let token = token.ok_or("missing token")?;
+    let (expires, signature) = token.split_once('.').ok_or("malformed token")?;
+    let expires = expires.parse::<i64>().map_err(|_| "malformed token")?;
+    let signature = hex::decode(signature).map_err(|_| "malformed token")?;
+    // 常数时间比较
+    mac(secret, domain, expires)
+        .verify_slice(&signature)
+        .map_err(|_| "bad signature")?;
+    if expires < now {
+        return Err("token expired");
+    }
+    Ok(())
+}
+
+fn mac(secret: &str, domain: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(format!("{}:{}", domain, expires).as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "a-secret-of-at-least-16-chars";
+    const DOMAIN: &str = "example.com";
+    const NOW: i64 = 1_760_000_000;
+
+    #[test]
+    fn valid_until_expiry() {
+        let token = issue(SECRET, DOMAIN, NOW + 60);
+        assert!(token.starts_with(&format!("{}.", NOW + 60)));
+        assert_eq!(verify(SECRET, DOMAIN, Some(&token), NOW), Ok(()));
+        assert_eq!(verify(SECRET, DOMAIN, Some(&token), NOW + 60), Ok(()));
+        // 同样的输入签出同样的令牌
+        assert_eq!(token, issue(SECRET, DOMAIN, NOW + 60));
+    }
+
+    #[test]
+    fn expired() {
+        let token = issue(SECRET, DOMAIN, NOW - 1);
+        assert_eq!(
+            verify(SECRET, DOMAIN, Some(&token), NOW),
+            Err("token expired")
+        );
+    }
+
+    #[test]
+    fn unsigned() {
+        assert_eq!(verify(SECRET, DOMAIN, None, NOW), Err("missing token"));
+    }
+
+    #[test]
+    fn wrong_domain_or_secret() {
+        let token = issue(SECRET, DOMAIN, NOW + 60);
+        assert_eq!(
+            verify(SECRET, "example.org", Some(&token), NOW),
+            Err("bad signature")
+        );
+        assert_eq!(
+            verify("another-secret-of-16-chars", DOMAIN, Some(&token), NOW),
+            Err("bad signature")
+        );
+    }
+
+    #[test]
+    fn tampered() {
+        let token = issue(SECRET, DOMAIN, NOW + 60);
+        let (expires, signature) = token.split_once('.').unwrap();
+        // 延长有效期
+        let extended = format!("{}.{}", NOW + 3600, signature);
+        assert_eq!(
+            verify(SECRET, DOMAIN, Some(&extended), NOW),
+            Err("bad signature")
+        );
+        // 改动签名的最后一位
+        let mut flipped = signature.to_string();
+        let last = if flipped.pop() == Some('0') { '1' } else { '0' };
+        flipped.push(last);
+        let flipped = format!("{}.{}", expires, flipped);
+        assert_eq!(
+            verify(SECRET, DOMAIN, Some(&flipped), NOW),
+            Err("bad signature")
+        );
+        // 截短的签名
+        let truncated = format!("{}.{}", expires, &signature[..32]);
+        assert_eq!(
+            verify(SECRET, DOMAIN, Some(&truncated), NOW),
+            Err("bad signature")
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        for token in [
+            "",
+            "no-dot",
+            "soon.abcd",
+            "1760000060.not-hex",
+            "1760000060.abc",
+            ".abcd",
+        ] {
+            assert_eq!(
+                verify(SECRET, DOMAIN, Some(token), NOW),
+                Err("malformed token"),
+                "{}",
+                token
+            );
+        }
+        assert_eq!(
+            verify(SECRET, DOMAIN, Some("1760000060."), NOW),
+            Err("bad signature")
+        );
+    }
+}
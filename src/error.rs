@@ -0,0 +1,129 @@
+use axum::{
+    http::{HeaderValue, StatusCode},
+    response::{Headers, IntoResponse, Response},
+    Json,
+};
+use tracing::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("not a member")]
+    NotMember,
+    #[error("system domain")]
+    SystemDomain,
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("admin disabled")]
+    AdminDisabled,
+    #[error("cross-origin request")]
+    CrossOrigin,
+    #[error("too many requests")]
+    RateLimited(u64),
+    #[error("missing or invalid header {0}")]
+    BadHeader(&'static str),
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+    #[error("database pool error: {0}")]
+    Pool(#[from] diesel::r2d2::PoolError),
+    #[error("template error: {0}")]
+    Template(#[from] askama::Error),
+    #[error("blocking task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("render error: {0}")]
+    Render(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotMember | AppError::AdminDisabled => StatusCode::NOT_FOUND,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::SystemDomain | AppError::CrossOrigin => StatusCode::FORBIDDEN,
+            AppError::BadHeader(_) => StatusCode::BAD_REQUEST,
+            AppError::Database(_)
+            | AppError::Pool(_)
+            | AppError::Template(_)
+            | AppError::Task(_)
+            | AppError::Render(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn add_headers(&self, res: &mut Response) {
+        match self {
+            // 让浏览器弹出登录框
+            AppError::Unauthorized => {
+                res.headers_mut().insert(
+                    "www-authenticate",
+                    HeaderValue::from_static(r#"Basic realm="boringbay", charset="UTF-8""#),
+                );
+            }
+            AppError::RateLimited(retry_after) => {
+                res.headers_mut()
+                    .insert("retry-after", HeaderValue::from(*retry_after));
+            }
+            _ => {}
+        }
+    }
+
+    // 内部错误只记日志，不向外暴露细节
+    fn public_message(&self) -> String {
+        let status = self.status();
+        if status.is_server_error() {
+            error!("{}", self);
+            return status
+                .canonical_reason()
+                .unwrap_or("internal error")
+                .to_lowercase();
+        }
+        self.to_string()
+    }
+}
+
+/// 页面与接口的错误以 JSON 返回
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let mut res = (
+            self.status(),
+            Json(serde_json::json!({ "error": self.public_message() })),
+        )
+            .into_response();
+        self.add_headers(&mut res);
+        res
+    }
+}
+
+/// 图片类接口的错误仍返回 SVG，避免嵌入处显示破图
+#[derive(Debug)]
+pub struct SvgError(pub AppError);
+
+impl From<AppError> for SvgError {
+    fn from(e: AppError) -> Self {
+        SvgError(e)
+    }
+}
+
+impl IntoResponse for SvgError {
+    fn into_response(self) -> Response {
+        let status = self.0.status();
+        let svg = format!(
+            r##"<?xml version="1.0" encoding="utf-8"?>
+<svg viewBox="0 0 240 20" xmlns="http://www.w3.org/2000/svg">
+  <text x="120" y="14" fill="#d0273e" font-size="12" text-anchor="middle" font-family="sans-serif">{} {}</text>
+</svg>"##,
+            status.as_u16(),
+            self.0.public_message()
+        );
+        let mut res = (
+            status,
+            Headers([
+                ("content-type", "image/svg+xml"),
+                ("cache-control", "no-store"),
+            ]),
+            svg,
+        )
+            .into_response();
+        self.0.add_headers(&mut res);
+        res
+    }
+}
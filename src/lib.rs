@@ -1,39 +1,202 @@
-use std::env;
-
-use chrono::{NaiveDateTime, Utc};
-use chrono_tz::Asia::Shanghai;
+use chrono::{NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use diesel::{
-    r2d2::{ConnectionManager, Pool},
+    connection::SimpleConnection,
+    r2d2::{ConnectionManager, CustomizeConnection, Pool, PooledConnection},
     SqliteConnection,
 };
-use lazy_static::lazy_static;
+use error::AppError;
 
+pub mod access;
+pub mod anomaly;
+pub mod anomaly_model;
 pub mod app_model;
 pub mod app_router;
 pub mod boring_face;
+pub mod bot;
+pub mod config;
+pub mod counter;
+pub mod dedup;
+pub mod embed_token;
+pub mod error;
 pub mod membership_model;
+pub mod rank_snapshot_model;
+pub mod raster;
+pub mod rate_limit;
 pub mod schema;
 pub mod statistics_model;
+pub mod svg;
+pub mod text_metrics;
 
 extern crate diesel;
 
 pub const GIT_HASH: &str = env!("GIT_HASH");
 
-// 系统域名，忽略 referrer 计数
-lazy_static! {
-    static ref SYSTEM_DOMAIN: String = env::var("SYSTEM_DOMAIN").unwrap();
-}
-
 pub type DbPool = Pool<ConnectionManager<SqliteConnection>>;
+pub type DbConn = PooledConnection<ConnectionManager<SqliteConnection>>;
+
+// 数据库操作在多个阻塞线程并发执行，写锁冲突时等待而不是直接报错
+#[derive(Debug)]
+struct BusyTimeout;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for BusyTimeout {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute("PRAGMA busy_timeout = 5000;")
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
 
-pub fn establish_connection(database_url: &str) -> DbPool {
+pub fn establish_connection(database_url: &str, pool_size: u32) -> Result<DbPool, anyhow::Error> {
     let manager = ConnectionManager::<SqliteConnection>::new(database_url);
     Pool::builder()
-        .max_size(5)
+        .max_size(pool_size)
+        .connection_customizer(Box::new(BusyTimeout))
         .build(manager)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+        .map_err(|e| anyhow::anyhow!("Error connecting to {}: {}", database_url, e))
+}
+
+/// diesel 是同步的，数据库操作统一放到阻塞线程池执行，避免卡住 tokio 工作线程
+pub async fn with_conn<F, T>(db_pool: &DbPool, f: F) -> Result<T, AppError>
+where
+    F: FnOnce(&mut DbConn) -> Result<T, AppError> + Send + 'static,
+    T: Send + 'static,
+{
+    let db_pool = db_pool.clone();
+    tokio::task::spawn_blocking(move || f(&mut db_pool.get()?)).await?
+}
+
+/// 数据库中的时间一律为 UTC
+pub fn now_utc() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+/// 本地时区当天零点对应的 UTC 时间，用作按天统计的分桶
+pub fn today_start(tz: Tz) -> NaiveDateTime {
+    day_start_of(tz, now_utc())
 }
 
-pub fn now_shanghai() -> NaiveDateTime {
-    Utc::now().with_timezone(&Shanghai).naive_local()
+/// UTC 时间所在本地日的零点（UTC 表示）
+pub fn day_start_of(tz: Tz, utc: NaiveDateTime) -> NaiveDateTime {
+    let local_date = tz.from_utc_datetime(&utc).naive_local().date();
+    // 夏令时可能跳过零点，取当天最早的合法时间
+    let midnight = NaiveDateTime::new(local_date, NaiveTime::from_hms(0, 0, 0));
+    match tz.from_local_datetime(&midnight).earliest() {
+        Some(start) => start.naive_utc(),
+        None => tz
+            .from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+            .earliest()
+            .map(|start| start.naive_utc())
+            .unwrap_or(midnight),
+    }
+}
+
+/// 两个本地日零点（UTC 表示）相隔的天数。
+/// 夏令时切换的日子只有 23 或 25 小时，按最接近的整天数计
+pub fn days_between(later: NaiveDateTime, earlier: NaiveDateTime) -> i64 {
+    let hours = (later - earlier).num_hours();
+    (hours + 12 * hours.signum()) / 24
+}
+
+/// 将 UTC 时间转为本地时间用于展示
+pub fn to_local(tz: Tz, utc: &NaiveDateTime) -> NaiveDateTime {
+    tz.from_utc_datetime(utc).naive_local()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    #[test]
+    fn day_start_without_dst() {
+        let tz = chrono_tz::Asia::Shanghai;
+        // 本地 2026-10-18 23:59 与 2026-10-19 00:00
+        assert_eq!(
+            day_start_of(tz, utc(2026, 10, 18, 15, 59)),
+            utc(2026, 10, 17, 16, 0)
+        );
+        assert_eq!(
+            day_start_of(tz, utc(2026, 10, 18, 16, 0)),
+            utc(2026, 10, 18, 16, 0)
+        );
+        assert_eq!(
+            day_start_of(chrono_tz::UTC, utc(2026, 10, 18, 23, 59)),
+            utc(2026, 10, 18, 0, 0)
+        );
+    }
+
+    #[test]
+    fn day_start_across_dst() {
+        let tz = chrono_tz::America::New_York;
+        // 2026-03-08 凌晨 2 点拨快，当天只有 23 小时
+        let spring = day_start_of(tz, utc(2026, 3, 8, 12, 0));
+        assert_eq!(spring, utc(2026, 3, 8, 5, 0));
+        assert_eq!(
+            day_start_of(tz, utc(2026, 3, 9, 12, 0)),
+            utc(2026, 3, 9, 4, 0)
+        );
+        // 当天最后一刻仍属于该日
+        assert_eq!(day_start_of(tz, utc(2026, 3, 9, 3, 59)), spring);
+        // 2026-11-01 凌晨 2 点拨慢，当天有 25 小时
+        let fall = day_start_of(tz, utc(2026, 11, 1, 12, 0));
+        assert_eq!(fall, utc(2026, 11, 1, 4, 0));
+        assert_eq!(day_start_of(tz, utc(2026, 11, 2, 4, 59)), fall);
+        assert_eq!(
+            day_start_of(tz, utc(2026, 11, 2, 5, 0)),
+            utc(2026, 11, 2, 5, 0)
+        );
+    }
+
+    #[test]
+    fn day_start_when_midnight_is_skipped() {
+        // 智利 2026-09-06 零点直接拨到 1 点，当天从本地 1 点（UTC-3）开始
+        let tz = chrono_tz::America::Santiago;
+        assert_eq!(
+            day_start_of(tz, utc(2026, 9, 6, 12, 0)),
+            utc(2026, 9, 6, 4, 0)
+        );
+        assert_eq!(
+            day_start_of(tz, utc(2026, 9, 6, 3, 59)),
+            utc(2026, 9, 5, 4, 0)
+        );
+    }
+
+    #[test]
+    fn today_start_is_current_local_day() {
+        for tz in [
+            chrono_tz::Asia::Shanghai,
+            chrono_tz::America::New_York,
+            chrono_tz::Europe::Berlin,
+        ] {
+            let before = now_utc();
+            let start = today_start(tz);
+            assert!(start <= before);
+            assert!(before - start < chrono::Duration::hours(25));
+            assert_eq!(to_local(tz, &start).date(), to_local(tz, &before).date());
+        }
+    }
+
+    #[test]
+    fn days_between_rounds_dst_days() {
+        for tz in [
+            chrono_tz::Asia::Shanghai,
+            chrono_tz::America::New_York,
+            chrono_tz::Europe::Berlin,
+            chrono_tz::America::Santiago,
+            chrono_tz::Australia::Lord_Howe,
+        ] {
+            // 从一年中逐日往回走，跨过两次夏令时切换
+            let today = day_start_of(tz, utc(2027, 1, 1, 12, 0));
+            let mut day = today;
+            for n in 0..400 {
+                assert_eq!(days_between(today, day), n, "{} {}", tz, day);
+                assert_eq!(days_between(day, today), -n, "{} {}", tz, day);
+                day = day_start_of(tz, day - chrono::Duration::hours(1));
+            }
+        }
+    }
 }
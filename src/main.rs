@@ -1,68 +1,340 @@
-use axum::{routing::get, AddExtensionLayer, Router};
-use chrono::{NaiveDateTime, NaiveTime};
+use anyhow::{anyhow, Context as _};
+use axum::{
+    extract::extractor_middleware,
+    routing::{get, post},
+    AddExtensionLayer, Router,
+};
+use clap::{Parser, Subcommand};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenv::dotenv;
 use naive::{
-    app_model::{Context, DynContext},
+    app_model::{save_statistics, Context, DynContext},
     app_router::{
-        home_page, join_us_page, rank_page, show_badge, show_favicon, show_icon, ws_upgrade,
+        admin_anomalies_page, beacon, embed_js, exclude_anomaly, home_page, include_anomaly,
+        join_us_page, rank_page, reload_access, render_badge, show_badge, show_favicon, show_icon,
+        show_manifest, ws_upgrade,
     },
-    establish_connection, now_shanghai,
+    config::Config,
+    establish_connection, now_utc,
+    rate_limit::{AdminGroup, BadgeGroup, FaviconGroup, IconGroup, PageGroup, RateLimit, WsGroup},
     statistics_model::Statistics,
-    DbPool,
+    today_start, DbPool,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
 };
-use std::{env, net::SocketAddr, sync::Arc};
 use tokio::signal;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations/");
 
+#[derive(Parser)]
+#[command(name = "naive", version, about = "无聊湾 🥱 The Boring Bay")]
+struct Cli {
+    /// 配置文件路径
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "CONFIG_PATH",
+        default_value = "./config.toml"
+    )]
+    config: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 执行数据库迁移并启动服务（默认）
+    Serve {
+        /// 监听地址，覆盖配置中的 server.bind
+        #[arg(short, long)]
+        bind: Option<SocketAddr>,
+    },
+    /// 管理数据库迁移
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// 导出统计数据为 JSON
+    Export {
+        /// 输出文件，缺省为标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 从 JSON 导入统计数据，按站点和日期覆盖
+    Import {
+        /// 由 export 生成的文件
+        input: PathBuf,
+    },
+    /// 校验配置文件并打印生效的配置
+    CheckConfig,
+    /// 为设置了嵌入密钥的站点签发徽章地址
+    EmbedToken {
+        /// 站点域名
+        domain: String,
+        /// 有效天数，覆盖配置中的 embed.token_ttl_days
+        #[arg(long)]
+        days: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// 执行所有未应用的迁移
+    Up,
+    /// 回滚最近一次迁移
+    Down,
+    /// 查看迁移状态
+    Status,
+}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
     tracing_subscriber::fmt::init();
 
-    let db_pool: DbPool = establish_connection(&env::var("DATABASE_URL").unwrap());
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {:#}", e);
+        process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), anyhow::Error> {
+    let mut config = Config::load(&cli.config)?;
+
+    match cli.command.unwrap_or(Command::Serve { bind: None }) {
+        Command::Serve { bind } => {
+            if let Some(bind) = bind {
+                config.server.bind = bind;
+            }
+            config.validate()?;
+            serve(config).await
+        }
+        Command::Migrate { action } => migrate(&connect(&config)?, action),
+        Command::Export { output } => export(&connect(&config)?, output.as_deref()),
+        Command::Import { input } => import(&connect(&config)?, &input),
+        Command::EmbedToken { domain, days } => {
+            config.validate_embed()?;
+            embed_token(&config, &domain, days)
+        }
+        Command::CheckConfig => {
+            config.validate()?;
+            println!("{:#?}", config);
+            println!("config ok");
+            Ok(())
+        }
+    }
+}
 
-    tracing::info!(
-        "migration {:?}",
-        db_pool
-            .get()
-            .unwrap()
-            .run_pending_migrations(MIGRATIONS)
-            .unwrap()
+fn embed_token(config: &Config, domain: &str, days: Option<i64>) -> Result<(), anyhow::Error> {
+    let secret = config
+        .embed
+        .secrets
+        .get(domain)
+        .ok_or_else(|| anyhow!("no embed secret configured for {}", domain))?;
+    let days = days.unwrap_or(config.embed.token_ttl_days);
+    let expires = now_utc() + chrono::Duration::days(days);
+    let token = naive::embed_token::issue(secret, domain, expires.timestamp());
+    println!(
+        r#"<a title="无聊湾 🥱 The Boring Bay" href="https://{0}"><img height="18px" src="https://{0}/api/badge/{1}?t={2}"></img></a>"#,
+        config.server.system_domain, domain, token
+    );
+    eprintln!(
+        "token expires at {} UTC",
+        expires.format("%Y-%m-%d %H:%M:%S")
     );
+    Ok(())
+}
 
-    let context = Arc::new(Context::default(db_pool).await) as DynContext;
+fn connect(config: &Config) -> Result<DbPool, anyhow::Error> {
+    config.validate_database()?;
+    establish_connection(&config.database.url, config.database.pool_size)
+}
+
+fn migrate(db_pool: &DbPool, action: MigrateAction) -> Result<(), anyhow::Error> {
+    let mut conn = db_pool.get()?;
+    match action {
+        MigrateAction::Up => {
+            let applied = conn
+                .run_pending_migrations(MIGRATIONS)
+                .map_err(|e| anyhow!("failed to run migrations: {}", e))?;
+            applied.iter().for_each(|v| println!("applied {}", v));
+            println!("{} migration(s) applied", applied.len());
+        }
+        MigrateAction::Down => {
+            let reverted = conn
+                .revert_last_migration(MIGRATIONS)
+                .map_err(|e| anyhow!("failed to revert migration: {}", e))?;
+            println!("reverted {}", reverted);
+        }
+        MigrateAction::Status => {
+            let applied = conn
+                .applied_migrations()
+                .map_err(|e| anyhow!("failed to read applied migrations: {}", e))?;
+            let pending = conn
+                .pending_migrations(MIGRATIONS)
+                .map_err(|e| anyhow!("failed to read pending migrations: {}", e))?;
+            let mut applied = applied.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            applied.sort();
+            applied.iter().for_each(|v| println!("[applied] {}", v));
+            pending
+                .iter()
+                .for_each(|m| println!("[pending] {}", m.name()));
+        }
+    }
+    Ok(())
+}
+
+fn export(db_pool: &DbPool, output: Option<&Path>) -> Result<(), anyhow::Error> {
+    let mut conn = db_pool.get()?;
+    let all = Statistics::all(&mut conn)?;
+    let content = serde_json::to_string_pretty(&all)?;
+    match output {
+        Some(path) => fs::write(path, content)
+            .with_context(|| format!("failed to write {}", path.display()))?,
+        None => writeln!(io::stdout(), "{}", content)?,
+    }
+    eprintln!("{} statistics exported", all.len());
+    Ok(())
+}
+
+fn import(db_pool: &DbPool, input: &Path) -> Result<(), anyhow::Error> {
+    let content =
+        fs::read_to_string(input).with_context(|| format!("failed to read {}", input.display()))?;
+    let all: Vec<Statistics> = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a valid export", input.display()))?;
+    let mut conn = db_pool.get()?;
+    for stat in all.iter() {
+        Statistics::insert_or_update(&mut conn, stat).with_context(|| {
+            format!(
+                "failed to import statistics of member {} at {}",
+                stat.membership_id, stat.created_at
+            )
+        })?;
+    }
+    eprintln!("{} statistics imported", all.len());
+    Ok(())
+}
+
+async fn serve(config: Config) -> Result<(), anyhow::Error> {
+    let db_pool = connect(&config)?;
+
+    let applied = tokio::task::spawn_blocking({
+        let db_pool = db_pool.clone();
+        move || -> Result<Vec<String>, anyhow::Error> {
+            let mut conn = db_pool.get()?;
+            let applied = conn
+                .run_pending_migrations(MIGRATIONS)
+                .map_err(|e| anyhow!("failed to run migrations: {}", e))?;
+            Ok(applied.iter().map(|v| v.to_string()).collect())
+        }
+    })
+    .await??;
+    tracing::info!("migration {:?}", applied);
+
+    let addr = config.server.bind;
+    let context = Arc::new(Context::default(db_pool, config).await?) as DynContext;
 
     // 定时存入数据库
     let ctx_clone = context.clone();
     tokio::spawn(async move {
-        ctx_clone.save_per_5_minutes().await;
+        ctx_clone.save_periodically().await;
     });
 
+    // 收到 SIGHUP 时重新加载访问名单
+    #[cfg(unix)]
+    {
+        let ctx_clone = context.clone();
+        let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())
+            .context("failed to install SIGHUP handler")?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                if let Err(e) = ctx_clone.reload_access() {
+                    tracing::error!("failed to reload access list: {:#}", e);
+                }
+            }
+        });
+    }
+
     let ctx_clone_for_shutdown = context.clone();
 
     let app = Router::new()
         .nest(
             "/api",
             Router::new()
-                .route("/badge/:domain", get(show_badge))
-                .route("/favicon/:domain", get(show_favicon))
-                .route("/icon/:domain", get(show_icon))
-                .route("/ws", get(ws_upgrade)),
+                .route(
+                    "/badge/:domain",
+                    get(show_badge).layer(extractor_middleware::<RateLimit<BadgeGroup>>()),
+                )
+                .route(
+                    "/beacon/:domain",
+                    get(beacon)
+                        .post(beacon)
+                        .layer(extractor_middleware::<RateLimit<BadgeGroup>>()),
+                )
+                .route(
+                    "/render/:domain",
+                    get(render_badge).layer(extractor_middleware::<RateLimit<BadgeGroup>>()),
+                )
+                .route(
+                    "/favicon/:domain",
+                    get(show_favicon).layer(extractor_middleware::<RateLimit<FaviconGroup>>()),
+                )
+                .route(
+                    "/manifest/:domain",
+                    get(show_manifest).layer(extractor_middleware::<RateLimit<FaviconGroup>>()),
+                )
+                .route(
+                    "/icon/:domain",
+                    get(show_icon).layer(extractor_middleware::<RateLimit<IconGroup>>()),
+                )
+                .route(
+                    "/ws",
+                    get(ws_upgrade).layer(extractor_middleware::<RateLimit<WsGroup>>()),
+                ),
+        )
+        .route(
+            "/",
+            get(home_page).layer(extractor_middleware::<RateLimit<PageGroup>>()),
+        )
+        .route(
+            "/join-us",
+            get(join_us_page).layer(extractor_middleware::<RateLimit<PageGroup>>()),
+        )
+        .route(
+            "/rank",
+            get(rank_page).layer(extractor_middleware::<RateLimit<PageGroup>>()),
+        )
+        .route(
+            "/embed.js",
+            get(embed_js).layer(extractor_middleware::<RateLimit<PageGroup>>()),
+        )
+        .nest(
+            "/admin",
+            Router::new()
+                .route("/anomalies", get(admin_anomalies_page))
+                .route("/anomalies/:id/exclude", post(exclude_anomaly))
+                .route("/anomalies/:id/include", post(include_anomaly))
+                .route("/access/reload", post(reload_access))
+                .layer(extractor_middleware::<RateLimit<AdminGroup>>()),
         )
-        .route("/", get(home_page))
-        .route("/join-us", get(join_us_page))
-        .route("/rank", get(rank_page))
         .layer(AddExtensionLayer::new(context));
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     tracing::debug!("listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+    axum::Server::try_bind(&addr)
+        .with_context(|| format!("failed to bind {}", addr))?
+        .serve(app.into_make_service_with_connect_info::<SocketAddr, _>())
         .with_graceful_shutdown(shutdown_signal(ctx_clone_for_shutdown))
-        .await
-        .unwrap();
+        .await?;
+    Ok(())
 }
 
 async fn shutdown_signal(ctx: Arc<Context>) {
@@ -90,24 +362,14 @@ async fn shutdown_signal(ctx: Arc<Context>) {
 
     println!("signal received, running cleanup tasks..");
 
-    let _today = NaiveDateTime::new(now_shanghai().date(), NaiveTime::from_hms(0, 0, 0));
-    let page_view_read = ctx.unique_visitor.read().await;
-    let referrer_read = ctx.referrer.read().await;
-    ctx.id2member.keys().for_each(|id| {
-        let uv = *page_view_read.get(id).unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-        let referrer = *referrer_read.get(id).unwrap_or(&(0, NaiveDateTime::from_timestamp(0, 0)));
-        Statistics::insert_or_update(
-            ctx.db_pool.get().unwrap(),
-            &Statistics {
-                created_at: _today,
-                membership_id: *id,
-                unique_visitor: uv.0,
-                updated_at: uv.1,
-                referrer: referrer.0,
-                latest_referrer_at: referrer.1,
-                id: 0,
-            },
-        )
-        .unwrap();
-    })
+    let _today = today_start(ctx.config.server.timezone);
+    let list = ctx
+        .counter
+        .snapshot()
+        .iter()
+        .map(|(id, count)| count.to_statistics(*id, _today))
+        .collect::<Vec<_>>();
+    if let Err(e) = save_statistics(&ctx.db_pool, list).await {
+        tracing::error!("failed to save statistics: {}", e);
+    }
 }
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::anomaly_model::Anomaly;
 use crate::statistics_model::Statistics;
 
 #[derive(Deserialize, Clone, Debug, Serialize)]
@@ -12,6 +13,9 @@ pub struct Membership {
     pub description: String,
     pub github_username: String,
     pub hidden: Option<bool>,
+    /// 品牌色（`#rgb` 或 `#rrggbb`），设置后徽章默认使用由其生成的配色
+    #[serde(default)]
+    pub brand_color: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Serialize)]
@@ -19,3 +23,9 @@ pub struct RankAndMembership {
     pub rank: Statistics,
     pub membership: Membership,
 }
+
+#[derive(Clone, Serialize)]
+pub struct AnomalyAndMembership {
+    pub anomaly: Anomaly,
+    pub membership: Membership,
+}
@@ -0,0 +1,55 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::{Queryable, SqliteConnection};
+
+use crate::error::AppError;
+use crate::schema::rank_snapshot::{self, dsl::*};
+
+/// 某天收盘时站点在总排行与月榜中的名次，`created_at` 为当日零点（UTC）
+#[derive(Queryable, Debug, Clone)]
+pub struct RankSnapshot {
+    pub id: i32,
+    pub membership_id: i64,
+    pub created_at: NaiveDateTime,
+    pub position: Option<i64>,
+    pub monthly_position: Option<i64>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = rank_snapshot)]
+pub struct NewRankSnapshot {
+    pub membership_id: i64,
+    pub created_at: NaiveDateTime,
+    pub position: Option<i64>,
+    pub monthly_position: Option<i64>,
+}
+
+impl RankSnapshot {
+    pub fn save_all(
+        conn: &mut SqliteConnection,
+        list: &[NewRankSnapshot],
+    ) -> Result<usize, AppError> {
+        let mut count = 0;
+        for item in list {
+            count += diesel::insert_into(rank_snapshot)
+                .values(item)
+                .on_conflict((membership_id, created_at))
+                .do_update()
+                .set((
+                    position.eq(item.position),
+                    monthly_position.eq(item.monthly_position),
+                ))
+                .execute(conn)?;
+        }
+        Ok(count)
+    }
+
+    pub fn of_day(
+        conn: &mut SqliteConnection,
+        day: NaiveDateTime,
+    ) -> Result<Vec<RankSnapshot>, AppError> {
+        Ok(rank_snapshot
+            .filter(created_at.eq(day))
+            .load::<RankSnapshot>(conn)?)
+    }
+}
@@ -0,0 +1,199 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use r_cache::cache::Cache;
+use resvg::{tiny_skia, usvg};
+use tracing::info;
+
+use crate::error::AppError;
+
+// 放大倍数与正方形边长的范围，放大倍数按 SCALE_STEP 取整，避免任意小数各占一份缓存
+const SCALE_STEP: f32 = 0.25;
+const MIN_SCALE: f32 = 0.25;
+const MAX_SCALE: f32 = 4.0;
+const MIN_SQUARE: u32 = 16;
+const MAX_SQUARE: u32 = 512;
+// favicon.ico 包含的尺寸
+const ICO_SIZES: [u32; 3] = [16, 32, 48];
+// 编译期嵌入的 Noto Sans（SIL Open Font License，见 resources/embedded），不依赖主机字体，各处渲染结果一致
+const EMBEDDED_FAMILY: &str = "Noto Sans";
+pub const EMBEDDED_FONTS: [&[u8]; 2] = [
+    include_bytes!("../resources/embedded/NotoSans-Bold.ttf"),
+    include_bytes!("../resources/embedded/NotoSans-BoldItalic.ttf"),
+];
+
+/// 把徽章 SVG 栅格化为 PNG 或 ico，结果按内容缓存，同样的数字与版式只渲染一次
+pub struct Rasterizer {
+    fontdb: Arc<usvg::fontdb::Database>,
+    cache: Cache<String, Arc<Vec<u8>>>,
+    ttl: Duration,
+}
+
+impl Rasterizer {
+    /// 加载嵌入的 Noto Sans，再加载 font_dir 下的字体与系统字体补足缺少的字形
+    pub fn new(font_dir: &str, ttl: Duration) -> Self {
+        let mut fontdb = usvg::fontdb::Database::new();
+        for font in EMBEDDED_FONTS {
+            fontdb.load_font_data(font.to_vec());
+        }
+        let embedded = fontdb.len();
+        fontdb.load_fonts_dir(Path::new(font_dir));
+        let bundled = fontdb.len() - embedded;
+        fontdb.load_system_fonts();
+        fontdb.set_sans_serif_family(EMBEDDED_FAMILY);
+        info!(
+            "{} embedded, {} bundled and {} system font faces loaded",
+            embedded,
+            bundled,
+            fontdb.len() - embedded - bundled
+        );
+        Self {
+            fontdb: Arc::new(fontdb),
+            cache: Cache::new(Some(ttl)),
+            ttl,
+        }
+    }
+
+    /// key 需能区分站点、数字、配色与版式，尺寸会自动并入
+    pub async fn render_png(
+        &self,
+        key: String,
+        svg: impl FnOnce() -> String,
+        size: Size,
+    ) -> Result<Arc<Vec<u8>>, AppError> {
+        let size = size.normalized();
+        self.cached(format!("{}:{:?}", key, size), svg, move |svg, fontdb| {
+            let pixmap = rasterize(svg, fontdb, size)?;
+            pixmap
+                .encode_png()
+                .map_err(|e| AppError::Render(e.to_string()))
+        })
+        .await
+    }
+
+    /// 多尺寸的 ico，包含 16、32、48 三种尺寸
+    pub async fn render_ico(
+        &self,
+        key: String,
+        svg: impl FnOnce() -> String,
+    ) -> Result<Arc<Vec<u8>>, AppError> {
+        self.cached(format!("{}:ico", key), svg, |svg, fontdb| {
+            let mut dir = ico::IconDir::new(ico::ResourceType::Icon);
+            for size in ICO_SIZES {
+                let pixmap = rasterize(svg, fontdb.clone(), Size::Square(size))?;
+                let png = pixmap
+                    .encode_png()
+                    .map_err(|e| AppError::Render(e.to_string()))?;
+                let image = ico::IconImage::read_png(Cursor::new(png))
+                    .map_err(|e| AppError::Render(e.to_string()))?;
+                dir.add_entry(
+                    ico::IconDirEntry::encode(&image)
+                        .map_err(|e| AppError::Render(e.to_string()))?,
+                );
+            }
+            let mut out = Vec::new();
+            dir.write(&mut out)
+                .map_err(|e| AppError::Render(e.to_string()))?;
+            Ok(out)
+        })
+        .await
+    }
+
+    // 命中缓存时不生成 SVG，未命中时在阻塞线程中渲染
+    async fn cached(
+        &self,
+        key: String,
+        svg: impl FnOnce() -> String,
+        render: impl FnOnce(&str, Arc<usvg::fontdb::Database>) -> Result<Vec<u8>, AppError>
+            + Send
+            + 'static,
+    ) -> Result<Arc<Vec<u8>>, AppError> {
+        if let Some(out) = self.cache.get(&key).await {
+            return Ok(out);
+        }
+        let svg = svg();
+        let fontdb = self.fontdb.clone();
+        let out = Arc::new(tokio::task::spawn_blocking(move || render(&svg, fontdb)).await??);
+        self.cache.set(key, out.clone(), Some(self.ttl)).await;
+        Ok(out)
+    }
+}
+
+/// 输出尺寸：按 SVG 原始尺寸放大，或居中缩放到正方形
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Size {
+    Scale(f32),
+    Square(u32),
+}
+
+impl Size {
+    /// 限定到允许的范围，放大倍数取最近的 0.25 倍
+    pub fn normalized(self) -> Self {
+        match self {
+            Size::Scale(scale) if scale.is_finite() => {
+                Size::Scale(((scale / SCALE_STEP).round() * SCALE_STEP).clamp(MIN_SCALE, MAX_SCALE))
+            }
+            Size::Scale(_) => Size::Scale(1.0),
+            Size::Square(size) => Size::Square(size.clamp(MIN_SQUARE, MAX_SQUARE)),
+        }
+    }
+}
+
+fn rasterize(
+    svg: &str,
+    fontdb: Arc<usvg::fontdb::Database>,
+    size: Size,
+) -> Result<tiny_skia::Pixmap, AppError> {
+    let options = usvg::Options {
+        fontdb,
+        font_resolver: usvg::FontResolver {
+            select_font: select_embedded(),
+            select_fallback: usvg::FontResolver::default_fallback_selector(),
+        },
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| AppError::Render(e.to_string()))?;
+    let (w, h) = (tree.size().width(), tree.size().height());
+    let (width, height, transform) = match size {
+        Size::Scale(scale) => (
+            (w * scale).ceil() as u32,
+            (h * scale).ceil() as u32,
+            tiny_skia::Transform::from_scale(scale, scale),
+        ),
+        Size::Square(side) => {
+            let scale = side as f32 / w.max(h);
+            (
+                side,
+                side,
+                tiny_skia::Transform::from_scale(scale, scale).post_translate(
+                    (side as f32 - w * scale) / 2.0,
+                    (side as f32 - h * scale) / 2.0,
+                ),
+            )
+        }
+    };
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| AppError::Render("empty image".to_string()))?;
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+// 徽章 CSS 中的字体列表面向浏览器，按主机字体解析会因机器而异，这里一律先用嵌入字体，
+// 嵌入字体缺少的字形（中文、emoji 等）再由默认的回退逻辑从其余字体中查找
+fn select_embedded() -> usvg::FontSelectionFn<'static> {
+    Box::new(|font, fontdb| {
+        let style = match font.style() {
+            usvg::FontStyle::Normal => usvg::fontdb::Style::Normal,
+            usvg::FontStyle::Italic => usvg::fontdb::Style::Italic,
+            usvg::FontStyle::Oblique => usvg::fontdb::Style::Oblique,
+        };
+        fontdb.query(&usvg::fontdb::Query {
+            families: &[usvg::fontdb::Family::Name(EMBEDDED_FAMILY)],
+            weight: usvg::fontdb::Weight(font.weight()),
+            stretch: usvg::fontdb::Stretch::Normal,
+            style,
+        })
+    })
+}
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequest, RequestParts},
+    response::IntoResponse,
+};
+use ipnet::IpNet;
+
+use crate::app_model::{mask_ip, DynContext};
+use crate::config::{Budget, RateLimitConfig};
+use crate::error::{AppError, SvgError};
+
+// 闲置的令牌桶多久清理一次
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+// WebSocket 连接数超限时建议的重试间隔（秒）
+const WS_RETRY_AFTER: u64 = 30;
+// 国家未知时的代码，与 Cloudflare 一致
+const UNKNOWN_COUNTRY: &str = "XX";
+
+/// 限流的路由分组，各组预算独立
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Badge,
+    Icon,
+    Favicon,
+    Ws,
+    Page,
+    Admin,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    budget: Budget,
+    state: Mutex<(HashMap<String, Bucket>, Instant)>,
+}
+
+impl Buckets {
+    fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            state: Mutex::new((HashMap::new(), Instant::now())),
+        }
+    }
+
+    // 取一个令牌，不足时返回需要等待的秒数
+    fn take(&self, ip: &str, now: Instant) -> Result<(), u64> {
+        let burst = self.budget.burst as f64;
+        let rate = self.budget.per_second;
+        let mut state = self.state.lock().unwrap();
+        let (buckets, swept) = &mut *state;
+
+        // 已回满的桶与新建无异，定期清掉
+        if now.duration_since(*swept) > SWEEP_INTERVAL {
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.updated).as_secs_f64() * rate < burst
+            });
+            *swept = now;
+        }
+
+        let bucket = buckets.entry(ip.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64)
+        }
+    }
+}
+
+#[derive(Default)]
+struct WsCount {
+    total: usize,
+    per_ip: HashMap<String, usize>,
+}
+
+/// 按客户端 IP 的令牌桶限流，以及 WebSocket 并发连接数限制
+pub struct RateLimiter {
+    enabled: bool,
+    groups: HashMap<RouteGroup, Buckets>,
+    ws_max_per_ip: usize,
+    ws_max_total: usize,
+    ws: Arc<Mutex<WsCount>>,
+    trusted_proxies: Vec<IpNet>,
+}
+
+/// 占用的 WebSocket 连接名额，连接结束时释放
+pub struct WsSlot {
+    ip: String,
+    ws: Arc<Mutex<WsCount>>,
+}
+
+impl Drop for WsSlot {
+    fn drop(&mut self) {
+        let mut ws = self.ws.lock().unwrap();
+        ws.total = ws.total.saturating_sub(1);
+        if let Some(n) = ws.per_ip.get_mut(&self.ip) {
+            *n -= 1;
+            if *n == 0 {
+                ws.per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let groups = [
+            (RouteGroup::Badge, &config.badge),
+            (RouteGroup::Icon, &config.icon),
+            (RouteGroup::Favicon, &config.favicon),
+            (RouteGroup::Ws, &config.ws),
+            (RouteGroup::Page, &config.page),
+            (RouteGroup::Admin, &config.admin),
+        ]
+        .into_iter()
+        .map(|(group, budget)| (group, Buckets::new(budget.clone())))
+        .collect();
+        Self {
+            enabled: config.enabled,
+            groups,
+            ws_max_per_ip: config.ws_max_per_ip,
+            ws_max_total: config.ws_max_total,
+            ws: Arc::new(Mutex::new(WsCount::default())),
+            // 启动时已校验
+            trusted_proxies: config
+                .trusted_proxies
+                .iter()
+                .filter_map(|c| c.parse().ok())
+                .collect(),
+        }
+    }
+
+    /// 连接来自可信代理时采信代理传来的 IP，否则使用连接地址，避免伪造请求头绕过限流与访问名单
+    pub fn client_ip(&self, peer: Option<IpAddr>, forwarded: Option<&str>) -> Option<String> {
+        match forwarded.map(str::trim).filter(|f| !f.is_empty()) {
+            Some(ip) if self.trusts(peer) => Some(ip.to_string()),
+            _ => peer.map(|p| p.to_string()),
+        }
+    }
+
+    /// 国家同样只采信可信代理传来的，否则为未知 `XX`
+    pub fn client_country(&self, peer: Option<IpAddr>, forwarded: Option<&str>) -> String {
+        match forwarded.map(str::trim).filter(|f| !f.is_empty()) {
+            Some(country) if self.trusts(peer) => country.to_string(),
+            _ => UNKNOWN_COUNTRY.to_string(),
+        }
+    }
+
+    fn trusts(&self, peer: Option<IpAddr>) -> bool {
+        peer.is_some_and(|p| self.trusted_proxies.iter().any(|n| n.contains(&p)))
+    }
+
+    pub fn check(&self, group: RouteGroup, ip: &str) -> Result<(), AppError> {
+        if !self.enabled {
+            return Ok(());
+        }
+        match self.groups.get(&group) {
+            Some(buckets) => buckets.take(ip, Instant::now()).map_err(|retry_after| {
+                tracing::info!("rate limited {:?} {}", group, mask_ip(ip));
+                AppError::RateLimited(retry_after)
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn acquire_ws(&self, ip: &str) -> Result<WsSlot, AppError> {
+        let mut ws = self.ws.lock().unwrap();
+        if self.enabled {
+            let current = ws.per_ip.get(ip).copied().unwrap_or_default();
+            if ws.total >= self.ws_max_total || current >= self.ws_max_per_ip {
+                tracing::info!(
+                    "too many websocket connections {} ({})",
+                    mask_ip(ip),
+                    ws.total
+                );
+                return Err(AppError::RateLimited(WS_RETRY_AFTER));
+            }
+        }
+        ws.total += 1;
+        *ws.per_ip.entry(ip.to_string()).or_default() += 1;
+        Ok(WsSlot {
+            ip: ip.to_string(),
+            ws: self.ws.clone(),
+        })
+    }
+}
+
+/// 客户端 IP 与国家。连接来自可信代理（默认 Cloudflare）时取 `CF-Connecting-IP` 与 `CF-IPCountry`，
+/// 否则 IP 为连接地址，国家未知。`HeaderMap` 提取器会取走请求头，处理函数中需放在它之前
+pub struct Client {
+    pub ip: String,
+    pub country: String,
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for Client {
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let header = |name| {
+            req.headers()
+                .and_then(|h| h.get(name))
+                .and_then(|v| v.to_str().ok())
+        };
+        let (forwarded_ip, forwarded_country) =
+            (header("CF-Connecting-IP"), header("CF-IPCountry"));
+        let extensions = req.extensions();
+        let peer = extensions
+            .and_then(|e| e.get::<ConnectInfo<SocketAddr>>())
+            .map(|c| c.0.ip());
+        let (ip, country) = match extensions.and_then(|e| e.get::<DynContext>()) {
+            Some(ctx) => (
+                ctx.limiter.client_ip(peer, forwarded_ip),
+                ctx.limiter.client_country(peer, forwarded_country),
+            ),
+            None => (peer.map(|p| p.to_string()), UNKNOWN_COUNTRY.to_string()),
+        };
+        Ok(Client {
+            ip: ip.unwrap_or_else(|| "unknown".to_string()),
+            country,
+        })
+    }
+}
+
+/// 只取客户端 IP，见 [`Client`]
+pub struct ClientIp(pub String);
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for ClientIp {
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Client { ip, .. } = Client::from_request(req).await?;
+        Ok(ClientIp(ip))
+    }
+}
+
+/// 路由分组标记
+pub trait Group {
+    const GROUP: RouteGroup;
+    type Rejection: From<AppError> + IntoResponse;
+}
+
+pub struct BadgeGroup;
+pub struct IconGroup;
+pub struct FaviconGroup;
+pub struct WsGroup;
+pub struct PageGroup;
+pub struct AdminGroup;
+
+// 图片类接口被限流时仍返回 SVG
+impl Group for BadgeGroup {
+    const GROUP: RouteGroup = RouteGroup::Badge;
+    type Rejection = SvgError;
+}
+
+impl Group for IconGroup {
+    const GROUP: RouteGroup = RouteGroup::Icon;
+    type Rejection = SvgError;
+}
+
+impl Group for FaviconGroup {
+    const GROUP: RouteGroup = RouteGroup::Favicon;
+    type Rejection = SvgError;
+}
+
+impl Group for WsGroup {
+    const GROUP: RouteGroup = RouteGroup::Ws;
+    type Rejection = AppError;
+}
+
+impl Group for PageGroup {
+    const GROUP: RouteGroup = RouteGroup::Page;
+    type Rejection = AppError;
+}
+
+impl Group for AdminGroup {
+    const GROUP: RouteGroup = RouteGroup::Admin;
+    type Rejection = AppError;
+}
+
+/// 限流提取器，配合 `extractor_middleware` 作为路由层使用
+pub struct RateLimit<G>(PhantomData<G>);
+
+#[async_trait]
+impl<B: Send, G: Group + Send + Sync + 'static> FromRequest<B> for RateLimit<G> {
+    type Rejection = G::Rejection;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let ClientIp(ip) = ClientIp::from_request(req).await?;
+        if let Some(ctx) = req.extensions().and_then(|e| e.get::<DynContext>()) {
+            ctx.limiter.check(G::GROUP, &ip)?;
+        }
+        Ok(RateLimit(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter_with(config: impl FnOnce(&mut RateLimitConfig)) -> RateLimiter {
+        let mut c = RateLimitConfig::default();
+        config(&mut c);
+        RateLimiter::new(&c)
+    }
+
+    #[test]
+    fn bucket_takes_burst_then_refills() {
+        let buckets = Buckets::new(Budget::new(3, 1.0));
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(buckets.take("203.0.113.1", start), Ok(()));
+        }
+        assert_eq!(buckets.take("203.0.113.1", start), Err(1));
+        // 各 IP 的桶互不影响
+        assert_eq!(buckets.take("203.0.113.2", start), Ok(()));
+        // 1.5 秒补回 1.5 个令牌
+        let later = start + Duration::from_millis(1500);
+        assert_eq!(buckets.take("203.0.113.1", later), Ok(()));
+        assert_eq!(buckets.take("203.0.113.1", later), Err(1));
+        // 闲置再久也不超过 burst
+        let idle = later + Duration::from_secs(3600);
+        for _ in 0..3 {
+            assert_eq!(buckets.take("203.0.113.1", idle), Ok(()));
+        }
+        assert!(buckets.take("203.0.113.1", idle).is_err());
+    }
+
+    #[test]
+    fn retry_after_follows_refill_rate() {
+        let buckets = Buckets::new(Budget::new(1, 0.2));
+        let now = Instant::now();
+        assert_eq!(buckets.take("203.0.113.1", now), Ok(()));
+        assert_eq!(buckets.take("203.0.113.1", now), Err(5));
+        assert_eq!(
+            buckets.take("203.0.113.1", now + Duration::from_secs(4)),
+            Err(1)
+        );
+    }
+
+    #[test]
+    fn full_buckets_are_swept() {
+        let buckets = Buckets::new(Budget::new(2, 1.0));
+        let start = Instant::now();
+        buckets.take("203.0.113.1", start).unwrap();
+        buckets.take("203.0.113.2", start).unwrap();
+        assert_eq!(buckets.state.lock().unwrap().0.len(), 2);
+        // 清理时已回满的桶被移除，只留下本次新建的
+        buckets
+            .take("203.0.113.3", start + SWEEP_INTERVAL * 2)
+            .unwrap();
+        let state = buckets.state.lock().unwrap();
+        assert_eq!(state.0.keys().collect::<Vec<_>>(), ["203.0.113.3"]);
+    }
+
+    #[test]
+    fn check_respects_groups_and_enabled() {
+        let limiter = limiter_with(|c| {
+            c.badge = Budget::new(1, 0.1);
+            c.icon = Budget::new(1, 0.1);
+        });
+        assert!(limiter.check(RouteGroup::Badge, "203.0.113.1").is_ok());
+        assert!(matches!(
+            limiter.check(RouteGroup::Badge, "203.0.113.1"),
+            Err(AppError::RateLimited(10))
+        ));
+        // 各组预算独立
+        assert!(limiter.check(RouteGroup::Icon, "203.0.113.1").is_ok());
+
+        let disabled = limiter_with(|c| {
+            c.enabled = false;
+            c.badge = Budget::new(1, 0.1);
+        });
+        for _ in 0..10 {
+            assert!(disabled.check(RouteGroup::Badge, "203.0.113.1").is_ok());
+        }
+    }
+
+    #[test]
+    fn ws_slots_are_capped_and_released() {
+        let limiter = limiter_with(|c| {
+            c.ws_max_per_ip = 2;
+            c.ws_max_total = 3;
+        });
+        let a1 = limiter.acquire_ws("203.0.113.1").unwrap();
+        let a2 = limiter.acquire_ws("203.0.113.1").unwrap();
+        assert!(matches!(
+            limiter.acquire_ws("203.0.113.1"),
+            Err(AppError::RateLimited(WS_RETRY_AFTER))
+        ));
+        let b1 = limiter.acquire_ws("203.0.113.2").unwrap();
+        // 总数已满
+        assert!(limiter.acquire_ws("203.0.113.3").is_err());
+
+        drop(a1);
+        let c1 = limiter.acquire_ws("203.0.113.3").unwrap();
+        drop((a2, b1, c1));
+        let ws = limiter.ws.lock().unwrap();
+        assert_eq!(ws.total, 0);
+        assert!(ws.per_ip.is_empty());
+    }
+
+    #[test]
+    fn forwarded_ip_is_trusted_only_from_proxies() {
+        let limiter = limiter_with(|c| {
+            c.trusted_proxies = vec!["10.0.0.0/8".to_string(), "2001:db8::/32".to_string()];
+        });
+        let proxy = Some("10.1.2.3".parse().unwrap());
+        let direct = Some("198.51.100.9".parse().unwrap());
+        let cases = [
+            (proxy, Some("203.0.113.1"), Some("203.0.113.1")),
+            (proxy, Some(" 203.0.113.1 "), Some("203.0.113.1")),
+            (
+                Some("2001:db8::1".parse().unwrap()),
+                Some("203.0.113.1"),
+                Some("203.0.113.1"),
+            ),
+            // 可信代理未传时退回连接地址
+            (proxy, None, Some("10.1.2.3")),
+            (proxy, Some(""), Some("10.1.2.3")),
+            // 直连的客户端伪造请求头无效
+            (direct, Some("203.0.113.1"), Some("198.51.100.9")),
+            (direct, None, Some("198.51.100.9")),
+            (None, Some("203.0.113.1"), None),
+        ];
+        for (peer, forwarded, expected) in cases {
+            assert_eq!(
+                limiter.client_ip(peer, forwarded).as_deref(),
+                expected,
+                "{:?} {:?}",
+                peer,
+                forwarded
+            );
+        }
+        assert_eq!(limiter.client_country(proxy, Some("CN")), "CN");
+        assert_eq!(limiter.client_country(proxy, None), UNKNOWN_COUNTRY);
+        assert_eq!(limiter.client_country(direct, Some("CN")), UNKNOWN_COUNTRY);
+    }
+}
@@ -1,5 +1,26 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    anomaly (id) {
+        id -> Integer,
+        membership_id -> BigInt,
+        created_at -> Timestamp,
+        reasons -> Text,
+        excluded -> Bool,
+        detected_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    rank_snapshot (id) {
+        id -> Integer,
+        membership_id -> BigInt,
+        created_at -> Timestamp,
+        position -> Nullable<BigInt>,
+        monthly_position -> Nullable<BigInt>,
+    }
+}
+
 diesel::table! {
     statistics (id) {
         id -> Integer,
@@ -9,5 +30,9 @@ diesel::table! {
         unique_visitor -> BigInt,
         referrer -> BigInt,
         latest_referrer_at -> Timestamp,
+        bot -> BigInt,
+        unverified -> BigInt,
     }
 }
+
+diesel::allow_tables_to_appear_in_same_query!(anomaly, rank_snapshot, statistics,);
@@ -1,11 +1,11 @@
 use std::ops::Sub;
 
-use crate::now_shanghai;
+use crate::error::AppError;
 use crate::schema::statistics::{self, dsl::*};
-use anyhow::anyhow;
-use chrono::{Duration, NaiveDateTime, NaiveTime};
+use crate::{day_start_of, today_start};
+use chrono::{Duration, NaiveDateTime};
+use chrono_tz::Tz;
 use diesel::dsl::sql;
-use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::sqlite::Sqlite;
 use diesel::{debug_query, prelude::*};
 use diesel::{Queryable, SqliteConnection};
@@ -21,11 +21,15 @@ pub struct Statistics {
     pub unique_visitor: i64,
     pub referrer: i64,
     pub latest_referrer_at: NaiveDateTime,
+    #[serde(default)]
+    pub bot: i64,
+    #[serde(default)]
+    pub unverified: i64,
 }
 
 impl Statistics {
     pub fn insert_or_update(
-        mut conn: PooledConnection<ConnectionManager<SqliteConnection>>,
+        conn: &mut SqliteConnection,
         stat: &Statistics,
     ) -> Result<usize, diesel::result::Error> {
         let statement = diesel::insert_into(statistics)
@@ -36,6 +40,8 @@ impl Statistics {
                 unique_visitor.eq(stat.unique_visitor),
                 referrer.eq(stat.referrer),
                 latest_referrer_at.eq(stat.latest_referrer_at),
+                bot.eq(stat.bot),
+                unverified.eq(stat.unverified),
             ))
             .on_conflict((membership_id, created_at))
             .do_update()
@@ -44,25 +50,21 @@ impl Statistics {
                 referrer.eq(stat.referrer),
                 updated_at.eq(stat.updated_at),
                 latest_referrer_at.eq(stat.latest_referrer_at),
+                bot.eq(stat.bot),
+                unverified.eq(stat.unverified),
             ));
         debug!("sql: {}", debug_query::<Sqlite, _>(&statement));
-        statement.execute(&mut conn)
+        statement.execute(conn)
     }
 
-    pub fn today(
-        conn: PooledConnection<ConnectionManager<SqliteConnection>>,
-    ) -> Result<Vec<Statistics>, anyhow::Error> {
-        load_statistics_by_created_at(
-            conn,
-            NaiveDateTime::new(now_shanghai().date(), NaiveTime::from_hms(0, 0, 0)),
-        )
+    pub fn today(conn: &mut SqliteConnection, tz: Tz) -> Result<Vec<Statistics>, AppError> {
+        load_statistics_by_created_at(conn, today_start(tz))
     }
 
-    pub fn prev_day_rank_avg(conn: PooledConnection<ConnectionManager<SqliteConnection>>) -> i64 {
+    pub fn prev_day_rank_avg(conn: &mut SqliteConnection, tz: Tz, level_divisor: i64) -> i64 {
         let res = load_statistics_by_created_at(
             conn,
-            NaiveDateTime::new(now_shanghai().date(), NaiveTime::from_hms(0, 0, 0))
-                .sub(Duration::hours(24)),
+            day_start_of(tz, today_start(tz).sub(Duration::hours(1))),
         );
         if let Ok(res) = res {
             let mut sum = 0;
@@ -75,7 +77,7 @@ impl Statistics {
                 }
             });
             if count > 0 {
-                let rank_svg = sum / count / 10;
+                let rank_svg = sum / count / level_divisor;
                 if rank_svg > 0 {
                     return rank_svg;
                 }
@@ -85,22 +87,28 @@ impl Statistics {
     }
 
     pub fn rank_between(
-        mut conn: PooledConnection<ConnectionManager<SqliteConnection>>,
+        conn: &mut SqliteConnection,
         start: NaiveDateTime,
         end: NaiveDateTime,
-    ) -> Result<Vec<Statistics>, anyhow::Error> {
+    ) -> Result<Vec<Statistics>, AppError> {
         let res = statistics
             .select((
                 membership_id,
                 sql::<diesel::sql_types::Timestamp>("MIN(created_at) as m_created_at"),
                 sql::<diesel::sql_types::BigInt>("SUM(unique_visitor) as s_unique_visitor"),
                 sql::<diesel::sql_types::BigInt>("SUM(referrer) as s_referrer"),
+                sql::<diesel::sql_types::BigInt>("SUM(bot) as s_bot"),
+                sql::<diesel::sql_types::BigInt>("SUM(unverified) as s_unverified"),
             ))
             .filter(created_at.between(start, end))
+            // 管理员排除的异常日不计入排行
+            .filter(sql::<diesel::sql_types::Bool>(
+                "NOT EXISTS (SELECT 1 FROM anomaly a WHERE a.membership_id = statistics.membership_id AND a.created_at = statistics.created_at AND a.excluded)",
+            ))
             .group_by(membership_id)
             .order_by(sql::<diesel::sql_types::BigInt>("s_referrer DESC"))
             .then_order_by(sql::<diesel::sql_types::BigInt>("s_unique_visitor DESC"))
-            .load::<(i64, NaiveDateTime, i64, i64)>(&mut conn);
+            .load::<(i64, NaiveDateTime, i64, i64, i64, i64)>(conn);
 
         let updated_at_list = statistics
             .select((
@@ -110,7 +118,7 @@ impl Statistics {
             .filter(updated_at.is_not_null().and(unique_visitor.gt(0)))
             .group_by(membership_id)
             .order(sql::<diesel::sql_types::Timestamp>("m_updated_at"))
-            .load::<(i64, NaiveDateTime)>(&mut conn);
+            .load::<(i64, NaiveDateTime)>(conn);
 
         let id_to_updated_at = updated_at_list
             .unwrap_or(Vec::new())
@@ -128,7 +136,7 @@ impl Statistics {
             .filter(latest_referrer_at.is_not_null().and(referrer.gt(0)))
             .group_by(membership_id)
             .order(sql::<diesel::sql_types::Timestamp>("m_latest_referrer_at"))
-            .load::<(i64, NaiveDateTime)>(&mut conn);
+            .load::<(i64, NaiveDateTime)>(conn);
 
         let id_to_latest_referrer_at = latest_referrer_at_list
             .unwrap_or(Vec::new())
@@ -136,56 +144,54 @@ impl Statistics {
             .map(|s| (s.0, s.1))
             .collect::<std::collections::HashMap<i64, NaiveDateTime>>();
 
-        match res {
-            Ok(all) => {
-                let mut result = Vec::new();
-                all.iter().for_each(|s| {
-                    result.push(Statistics {
-                        id: 0,
-                        created_at: s.1,
-                        updated_at: id_to_updated_at
-                            .get(&s.0)
-                            .unwrap_or(&NaiveDateTime::from_timestamp(0, 0))
-                            .to_owned(),
-                        latest_referrer_at: id_to_latest_referrer_at
-                            .get(&s.0)
-                            .unwrap_or(&NaiveDateTime::from_timestamp(0, 0))
-                            .to_owned(),
-                        membership_id: s.0,
-                        unique_visitor: s.2,
-                        referrer: s.3,
-                    })
-                });
-                Ok(result)
-            }
-            Err(e) => Err(anyhow!("{:?}", e)),
-        }
+        let mut result = Vec::new();
+        res?.iter().for_each(|s| {
+            result.push(Statistics {
+                id: 0,
+                created_at: s.1,
+                updated_at: id_to_updated_at
+                    .get(&s.0)
+                    .unwrap_or(&NaiveDateTime::from_timestamp(0, 0))
+                    .to_owned(),
+                latest_referrer_at: id_to_latest_referrer_at
+                    .get(&s.0)
+                    .unwrap_or(&NaiveDateTime::from_timestamp(0, 0))
+                    .to_owned(),
+                membership_id: s.0,
+                unique_visitor: s.2,
+                referrer: s.3,
+                bot: s.4,
+                unverified: s.5,
+            })
+        });
+        Ok(result)
     }
 
-    pub fn all(
-        mut conn: PooledConnection<ConnectionManager<SqliteConnection>>,
-    ) -> Result<Vec<Statistics>, anyhow::Error> {
-        let res = statistics.load::<Statistics>(&mut conn);
-        match res {
-            Ok(all) => Ok(all),
-            Err(e) => Err(anyhow!("{:?}", e)),
-        }
+    /// 区间内的逐日统计
+    pub fn between(
+        conn: &mut SqliteConnection,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<Statistics>, AppError> {
+        Ok(statistics
+            .filter(created_at.between(start, end))
+            .load::<Statistics>(conn)?)
+    }
+
+    pub fn all(conn: &mut SqliteConnection) -> Result<Vec<Statistics>, AppError> {
+        Ok(statistics.load::<Statistics>(conn)?)
     }
 }
 
 fn load_statistics_by_created_at(
-    mut conn: PooledConnection<ConnectionManager<SqliteConnection>>,
+    conn: &mut SqliteConnection,
     _created_at: NaiveDateTime,
-) -> Result<Vec<Statistics>, anyhow::Error> {
+) -> Result<Vec<Statistics>, AppError> {
     debug!(
         "sql: {}",
         debug_query::<Sqlite, _>(&statistics.filter(created_at.eq(_created_at)))
     );
-    let res = statistics
+    Ok(statistics
         .filter(created_at.eq(_created_at))
-        .load::<Statistics>(&mut conn);
-    match res {
-        Ok(all) => Ok(all),
-        Err(e) => Err(anyhow!("{:?}", e)),
-    }
+        .load::<Statistics>(conn)?)
 }
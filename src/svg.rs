@@ -0,0 +1,118 @@
+use std::fmt::Write;
+
+/// SVG 元素，属性值与文字在输出时统一转义
+#[derive(Debug, Clone)]
+pub struct Element {
+    name: &'static str,
+    attrs: Vec<(&'static str, String)>,
+    children: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            attrs: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn attr(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.attrs.push((name, value.to_string()));
+        self
+    }
+
+    pub fn child(mut self, child: Element) -> Self {
+        self.children.push(Node::Element(child));
+        self
+    }
+
+    pub fn children(mut self, children: impl IntoIterator<Item = Element>) -> Self {
+        self.children
+            .extend(children.into_iter().map(Node::Element));
+        self
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.children.push(Node::Text(text.into()));
+        self
+    }
+
+    /// 输出带 XML 声明的完整文档
+    pub fn to_document(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        self.write_to(&mut out);
+        out
+    }
+
+    fn write_to(&self, out: &mut String) {
+        let _ = write!(out, "<{}", self.name);
+        for (name, value) in &self.attrs {
+            let _ = write!(out, " {}=\"{}\"", name, escape(value));
+        }
+        if self.children.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        for child in &self.children {
+            match child {
+                Node::Element(e) => e.write_to(out),
+                Node::Text(t) => out.push_str(&escape(t)),
+            }
+        }
+        let _ = write!(out, "</{}>", self.name);
+    }
+}
+
+/// 转义 XML 特殊字符，并去掉 XML 中不允许出现的控制字符
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_special_and_control_chars() {
+        assert_eq!(
+            escape("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+        assert_eq!(escape("a\u{0}b\u{7}c\u{1b}d"), "abcd");
+        assert_eq!(escape("a\tb\nc\r"), "a\tb\nc\r");
+        assert_eq!(escape("无聊湾 🥱"), "无聊湾 🥱");
+    }
+
+    #[test]
+    fn attributes_and_text_are_escaped() {
+        let svg = Element::new("text")
+            .attr("data-name", "\"><script>")
+            .text("</text><script>alert(1)</script>")
+            .to_document();
+        assert_eq!(
+            svg,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<text data-name=\"&quot;&gt;&lt;script&gt;\">&lt;/text&gt;&lt;script&gt;alert(1)&lt;/script&gt;</text>"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use lazy_static::lazy_static;
+use skrifa::{
+    charmap::Charmap,
+    instance::{LocationRef, Size},
+    metrics::GlyphMetrics,
+    FontRef, MetadataProvider,
+};
+
+use crate::raster::EMBEDDED_FONTS;
+
+// PNG 徽章用嵌入的 Noto Sans 绘制文字，字宽直接取自该字体
+struct Face {
+    charmap: Charmap<'static>,
+    glyphs: GlyphMetrics<'static>,
+}
+
+lazy_static! {
+    static ref FACES: Vec<Face> = EMBEDDED_FONTS
+        .iter()
+        .map(|data| {
+            let font = FontRef::new(data).expect("embedded font is valid");
+            Face {
+                charmap: font.charmap(),
+                // 字号为 1 时的字宽即以字号为单位
+                glyphs: font.glyph_metrics(Size::new(1.0), LocationRef::default()),
+            }
+        })
+        .collect();
+}
+
+/// 单个字符的宽度，单位为字号
+fn char_width(c: char) -> f64 {
+    let cp = c as u32;
+    match cp {
+        // 控制字符、组合附加符号、零宽字符、变体选择符、肤色修饰与标签字符不占宽度
+        0x00..=0x1f
+        | 0x7f..=0x9f
+        | 0x0300..=0x036f
+        | 0x200b..=0x200f
+        | 0xfe00..=0xfe0f
+        | 0x1f3fb..=0x1f3ff
+        | 0xe0000..=0xe007f => return 0.0,
+        _ => {}
+    }
+    // 粗体与粗斜体取较宽者
+    let embedded = FACES
+        .iter()
+        .filter_map(|f| f.glyphs.advance_width(f.charmap.map(c)?))
+        .fold(None, |w: Option<f32>, a| Some(w.map_or(a, |w| w.max(a))));
+    if let Some(width) = embedded {
+        return width as f64;
+    }
+    // 嵌入字体没有的字形由其余字体补足，按常见字宽估计
+    match cp {
+        // emoji 通常比全角字略宽
+        0x2600..=0x27bf | 0x1f000..=0x1faff => 1.2,
+        // 中日韩文字、全角符号
+        0x1100..=0x115f
+        | 0x2e80..=0x303f
+        | 0x3040..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x20000..=0x3fffd => 1.0,
+        // 其余字母按平均字宽估计
+        _ => 0.62,
+    }
+}
+
+/// 估计文字在给定字号下的宽度，宁宽勿窄
+pub fn text_width(text: &str, font_size: f64) -> f64 {
+    text.chars().map(char_width).sum::<f64>() * font_size
+}
+
+/// 在 max_width 内放下文字所需的字号，不小于 min_size，返回字号与该字号下的宽度
+pub fn fit(text: &str, font_size: f64, min_size: f64, max_width: f64) -> (f64, f64) {
+    let width = text_width(text, font_size);
+    if width <= max_width {
+        return (font_size, width);
+    }
+    let size = (font_size * max_width / width).max(min_size);
+    (size, text_width(text, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resvg::usvg;
+
+    // 用嵌入字体实际排版，取文字墨迹的宽度
+    fn rendered_width(text: &str, font_size: f64, italic: bool) -> f32 {
+        let mut fontdb = usvg::fontdb::Database::new();
+        for font in EMBEDDED_FONTS {
+            fontdb.load_font_data(font.to_vec());
+        }
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="4000" height="200"><text x="0" y="100" font-family="Noto Sans" font-weight="900" font-style="{}" font-size="{}">{}</text></svg>"#,
+            if italic { "italic" } else { "normal" },
+            font_size,
+            text
+        );
+        let options = usvg::Options {
+            fontdb: std::sync::Arc::new(fontdb),
+            ..Default::default()
+        };
+        let tree = usvg::Tree::from_str(&svg, &options).unwrap();
+        tree.root().abs_bounding_box().width()
+    }
+
+    #[test]
+    fn ascii_matches_embedded_font() {
+        for (text, italic) in [
+            ("The Boring Bay", false),
+            ("UV: 987654321  Referer: 123456789  Level: 10", true),
+            ("WWWWWWWWWW", false),
+            ("iiiiiiiiii", true),
+            ("lifelonglearn.ing", false),
+        ] {
+            let estimated = text_width(text, 38.0) as f32;
+            let rendered = rendered_width(text, 38.0, italic);
+            // 估计值是字宽之和，墨迹不含首尾的留白，应略窄但相差不多
+            assert!(
+                rendered <= estimated && rendered >= estimated * 0.9,
+                "{}: estimated {} rendered {}",
+                text,
+                estimated,
+                rendered
+            );
+        }
+    }
+
+    #[test]
+    fn latin_outside_ascii_uses_embedded_font() {
+        // Noto Sans 覆盖拉丁扩展、希腊与西里尔字母
+        for c in ['é', 'ß', 'Ω', 'Ж'] {
+            let w = char_width(c);
+            assert!(w > 0.3 && w < 1.0 && w != 0.62, "{} {}", c, w);
+        }
+    }
+
+    #[test]
+    fn cjk_and_emoji_fall_back_to_estimates() {
+        assert_eq!(text_width("无聊湾", 10.0), 30.0);
+        assert_eq!(text_width("한글", 10.0), 20.0);
+        assert_eq!(text_width("ＡＢ", 10.0), 20.0);
+        assert_eq!(text_width("🥱🚀", 10.0), 24.0);
+        // 变体选择符、肤色修饰与零宽连接符不占宽度
+        assert_eq!(text_width("✨\u{fe0f}", 10.0), 12.0);
+        assert_eq!(text_width("👍🏽", 10.0), 12.0);
+        assert_eq!(text_width("a\u{200b}b", 10.0), text_width("ab", 10.0));
+        assert_eq!(text_width("\u{7}\u{1b}", 10.0), 0.0);
+    }
+
+    #[test]
+    fn width_scales_with_font_size() {
+        let text = "无聊湾 🥱 Boring";
+        assert!((text_width(text, 20.0) - 2.0 * text_width(text, 10.0)).abs() < 1e-9);
+        assert_eq!(text_width("", 20.0), 0.0);
+    }
+
+    #[test]
+    fn fit_keeps_size_when_text_fits() {
+        let (size, width) = fit("Boring", 38.0, 26.0, 1000.0);
+        assert_eq!(size, 38.0);
+        assert_eq!(width, text_width("Boring", 38.0));
+    }
+
+    #[test]
+    fn fit_shrinks_to_max_width() {
+        let text = "一个名字很长的博客 The Boring Bay";
+        let max = text_width(text, 38.0) * 0.8;
+        let (size, width) = fit(text, 38.0, 26.0, max);
+        assert!((26.0..38.0).contains(&size));
+        assert!((width - max).abs() < 1e-6, "{} {}", width, max);
+    }
+
+    #[test]
+    fn fit_stops_at_min_size() {
+        let text = "一个名字特别特别长的中文博客站点 🥱🚀✨ The Boring Bay";
+        let (size, width) = fit(text, 38.0, 26.0, 100.0);
+        assert_eq!(size, 26.0);
+        // 缩到下限仍放不下，由调用方加宽徽章
+        assert!(width > 100.0);
+        assert_eq!(width, text_width(text, 26.0));
+    }
+}